@@ -0,0 +1,146 @@
+# English UI text, see ro.ftl for the other language.
+
+window-title-stars = Starhigh! Catch as many stars as you can!
+window-title-lives = Starhigh! If you miss a star, you lose a life.
+window-title-hearts = Starhigh! If you miss a heart, you lose a point.
+
+help =
+    { $left } / ← left
+    { $right } / → right
+    { $pause } pause
+    { $start } start
+    { $language } language
+    Esc settings
+    F2 achievements
+    F3 statistics
+
+settings-title = Settings
+settings-hint =
+    ↑/↓ choose, ←/→ change
+    Enter changes a key, Esc closes
+settings-press-key = press a key
+settings-on = on
+settings-off = off
+settings-language = Language
+settings-difficulty = Difficulty
+settings-mode = Mode
+settings-players = Players
+settings-grace-period = Grace period
+settings-window-mode = Window
+settings-vsync = VSync
+settings-background = Background
+settings-letterbox = Black bars
+settings-theme = Theme
+settings-move-left = Move left
+settings-move-right = Move right
+settings-pause = Pause
+settings-start = Start
+settings-switch-language = Switch language
+
+difficulty-easy = easy
+difficulty-normal = normal
+difficulty-hard = hard
+
+mode-classic = classic
+mode-time-attack-60 = time attack, 60s
+mode-time-attack-120 = time attack, 120s
+mode-zen = zen, practice
+mode-daily = daily challenge
+mode-campaign = campaign
+mode-practice-hud = practice
+mode-zen-hud =
+    zen
+    +/- speed
+
+level-label =
+    level { $number }: { $title }
+    { $status }
+level-first-steps = first steps
+level-steady-hands = steady hands
+level-obstacle-course = obstacle course
+level-storm-front = storm front
+goal-catch = catch { $stars } stars
+goal-catch-without-missing = catch { $stars } stars without missing
+goal-survive = survive for { $seconds }s
+level-won = level complete!
+level-lost = level failed
+
+event-meteor-shower = meteor shower!
+event-storm = storm!
+event-night = night falls
+
+achievements-title = Achievements ({ $earned }/{ $total })
+achievements-hint = F2 closes
+achievement-unlocked = achievement unlocked: { $title }
+achievement-first-catch = First catch
+achievement-first-catch-description = Catch a star
+achievement-centurion = Centurion
+achievement-centurion-description = Catch 100 stars in one game
+achievement-heart-collector = Heart collector
+achievement-heart-collector-description = Catch 5 hearts in one game
+achievement-on-a-roll = On a roll
+achievement-on-a-roll-description = Catch 25 stars in a row
+achievement-hanging-by-a-thread = Hanging by a thread
+achievement-hanging-by-a-thread-description = Play on for 60 seconds with a single life
+achievement-speed-demon = Speed demon
+achievement-speed-demon-description = Keep up until stars fall at speed 5
+
+stats-title = Statistics
+stats-hint = F3 closes
+stats-games-played = games played
+stats-stars-caught = stars caught
+stats-stars-missed = stars missed
+stats-hearts-caught = hearts caught
+stats-average-score = average score
+stats-median-score = median recent score
+stats-longest-game = longest game
+stats-peak-speed = peak speed
+stats-scores-histogram = recent scores
+stats-lengths-histogram = recent game lengths
+
+game-over-title = Game over
+game-over-score = score: { $score }
+game-over-new-best = new best!
+game-over-caught-missed = stars caught: { $caught }, missed: { $missed }
+game-over-time = time: { $time }
+game-over-max-speed = max speed: { $speed }
+game-over-max-combo = max combo: { $combo }
+game-over-session = this session: { $games } games, best { $best }
+game-over-retry = Retry
+game-over-menu = Menu
+game-over-share-seed = Share seed
+game-over-seed = play this run again with
+    --seed { $seed } --mode { $mode } --difficulty { $difficulty }
+game-over-hint = ↑/↓ choose, Enter confirm
+
+pause-title = Paused
+pause-resume = Resume
+pause-end-game = End game
+pause-hint =
+    ↑/↓ choose, Enter confirm
+    Esc settings
+
+theme-day = Day
+theme-night = Night
+theme-space = Space
+theme-underwater = Underwater
+
+versus-opponent =
+    rival
+    { $score } ♥{ $lives }
+versus-opponent-out =
+    rival out
+    { $score }
+versus-won =
+    rival { $score }
+    you won!
+versus-lost =
+    rival { $score }
+    you lost
+versus-draw =
+    rival { $score }
+    draw
+versus-disconnected = rival left
+
+window-mode-windowed = windowed
+window-mode-fullscreen = fullscreen
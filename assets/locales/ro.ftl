@@ -0,0 +1,146 @@
+# Textul interfeței în română, vezi en.ftl pentru cealaltă limbă.
+
+window-title-stars = Starhigh! Colectează cât mai multe stele!
+window-title-lives = Starhigh! Dacă nu prinzi o stea, pierzi o viață.
+window-title-hearts = Starhigh! Dacă nu prinzi o inimă, pierzi un punct.
+
+help =
+    { $left } / ← stânga
+    { $right } / → dreapta
+    { $pause } pauză
+    { $start } start
+    { $language } limba
+    Esc setări
+    F2 realizări
+    F3 statistici
+
+settings-title = Setări
+settings-hint =
+    ↑/↓ alege, ←/→ schimbă
+    Enter schimbă o tastă, Esc închide
+settings-press-key = apasă o tastă
+settings-on = pornit
+settings-off = oprit
+settings-language = Limba
+settings-difficulty = Dificultate
+settings-mode = Mod
+settings-players = Jucători
+settings-grace-period = Perioadă de grație
+settings-window-mode = Fereastră
+settings-vsync = VSync
+settings-background = Fundal
+settings-letterbox = Benzi negre
+settings-theme = Temă
+settings-move-left = Stânga
+settings-move-right = Dreapta
+settings-pause = Pauză
+settings-start = Start
+settings-switch-language = Schimbă limba
+
+difficulty-easy = ușor
+difficulty-normal = normal
+difficulty-hard = greu
+
+mode-classic = clasic
+mode-time-attack-60 = contra cronometru, 60s
+mode-time-attack-120 = contra cronometru, 120s
+mode-zen = zen, antrenament
+mode-daily = provocarea zilei
+mode-campaign = campanie
+mode-practice-hud = antrenament
+mode-zen-hud =
+    zen
+    +/- viteză
+
+level-label =
+    nivelul { $number }: { $title }
+    { $status }
+level-first-steps = primii pași
+level-steady-hands = mâini sigure
+level-obstacle-course = traseu cu obstacole
+level-storm-front = front de furtună
+goal-catch = prinde { $stars } stele
+goal-catch-without-missing = prinde { $stars } stele fără să ratezi
+goal-survive = rezistă { $seconds }s
+level-won = nivel terminat!
+level-lost = nivel pierdut
+
+event-meteor-shower = ploaie de meteori!
+event-storm = furtună!
+event-night = se lasă noaptea
+
+achievements-title = Realizări ({ $earned }/{ $total })
+achievements-hint = F2 închide
+achievement-unlocked = realizare deblocată: { $title }
+achievement-first-catch = Prima stea
+achievement-first-catch-description = Prinde o stea
+achievement-centurion = Centurion
+achievement-centurion-description = Prinde 100 de stele într-un joc
+achievement-heart-collector = Colecționar de inimi
+achievement-heart-collector-description = Prinde 5 inimi într-un joc
+achievement-on-a-roll = În formă
+achievement-on-a-roll-description = Prinde 25 de stele la rând
+achievement-hanging-by-a-thread = Atârnând de un fir
+achievement-hanging-by-a-thread-description = Joacă 60 de secunde cu o singură viață
+achievement-speed-demon = Vitezomanul
+achievement-speed-demon-description = Ține pasul până când stelele cad cu viteza 5
+
+stats-title = Statistici
+stats-hint = F3 închide
+stats-games-played = jocuri jucate
+stats-stars-caught = stele prinse
+stats-stars-missed = stele ratate
+stats-hearts-caught = inimi prinse
+stats-average-score = scor mediu
+stats-median-score = scor median recent
+stats-longest-game = cel mai lung joc
+stats-peak-speed = viteza maximă
+stats-scores-histogram = scoruri recente
+stats-lengths-histogram = durata jocurilor recente
+
+game-over-title = Joc terminat
+game-over-score = scor: { $score }
+game-over-new-best = record nou!
+game-over-caught-missed = stele prinse: { $caught }, ratate: { $missed }
+game-over-time = timp: { $time }
+game-over-max-speed = viteza maximă: { $speed }
+game-over-max-combo = combo maxim: { $combo }
+game-over-session = în sesiunea asta: { $games } jocuri, maxim { $best }
+game-over-retry = Din nou
+game-over-menu = Meniu
+game-over-share-seed = Împarte seed-ul
+game-over-seed = joacă din nou cu
+    --seed { $seed } --mode { $mode } --difficulty { $difficulty }
+game-over-hint = ↑/↓ alege, Enter confirmă
+
+pause-title = Pauză
+pause-resume = Continuă
+pause-end-game = Termină jocul
+pause-hint =
+    ↑/↓ alege, Enter confirmă
+    Esc setări
+
+theme-day = Zi
+theme-night = Noapte
+theme-space = Spațiu
+theme-underwater = Sub apă
+
+versus-opponent =
+    rival
+    { $score } ♥{ $lives }
+versus-opponent-out =
+    rival ieșit
+    { $score }
+versus-won =
+    rival { $score }
+    ai câștigat!
+versus-lost =
+    rival { $score }
+    ai pierdut
+versus-draw =
+    rival { $score }
+    egalitate
+versus-disconnected = rivalul a plecat
+
+window-mode-windowed = fereastră
+window-mode-fullscreen = ecran complet
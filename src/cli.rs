@@ -0,0 +1,312 @@
+use crate::{
+    localization::Language,
+    settings::{Difficulty, GameMode, Settings, SettingsOverrides, WindowMode},
+};
+use std::{net::SocketAddr, path::PathBuf};
+
+pub static USAGE: &str = "\
+Usage: starhigh-rs [OPTIONS]
+
+Options:
+    --seed N                 Use N to seed the order in which objects fall
+    --difficulty LEVEL       Play on easy, normal or hard
+    --mode MODE              Play classic, time-attack-60, time-attack-120, zen, daily or
+                             campaign games
+    --windowed               Run in a window
+    --fullscreen             Run in fullscreen
+    --headless               Run without a window, requires --ticks
+    --ticks N                Stop after N frames, requires --headless
+    --replay FILE            Play back the inputs recorded in FILE
+    --data-dir DIR           Look for assets overriding the built-in ones in DIR
+    --lang LANG              Show the UI in ro or en
+    --host [ADDRESS:]PORT    Wait for an opponent to play against on PORT, from this
+                             computer only unless ADDRESS says otherwise
+    --connect ADDRESS        Play against the opponent hosting at ADDRESS
+    --reset-highscore        Set the high score back to 0
+    -h, --help               Print this message";
+
+/// The command line arguments. Anything left unspecified falls back to the saved settings.
+#[derive(Debug, Default, PartialEq)]
+pub struct Args {
+    pub seed: Option<u64>,
+    pub difficulty: Option<Difficulty>,
+    pub mode: Option<GameMode>,
+    pub window_mode: Option<WindowMode>,
+    pub headless: bool,
+    pub ticks: Option<u64>,
+    pub replay: Option<PathBuf>,
+    pub data_dir: Option<PathBuf>,
+    pub language: Option<Language>,
+    pub host: Option<SocketAddr>,
+    pub connect: Option<String>,
+    pub reset_highscore: bool,
+    pub help: bool,
+}
+
+impl Args {
+    /// Parses `args`, which should not include the name of the executable.
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            // Both `--flag value` and `--flag=value` are accepted
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => {
+                    (flag.to_string(), Some(value.to_string()))
+                }
+                _ => (arg, None),
+            };
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| format!("{} needs a value", flag))
+            };
+
+            match flag.as_str() {
+                "--seed" => parsed.seed = Some(parse_number(&flag, &value()?)?),
+                "--difficulty" => {
+                    let level = value()?;
+                    parsed.difficulty = Some(
+                        Difficulty::from_name(&level)
+                            .ok_or_else(|| format!("unknown difficulty {}", level))?,
+                    );
+                }
+                "--mode" => {
+                    let name = value()?;
+                    parsed.mode = Some(
+                        GameMode::from_name(&name)
+                            .ok_or_else(|| format!("unknown mode {}", name))?,
+                    );
+                }
+                "--windowed" => parsed.set_window_mode(WindowMode::Windowed)?,
+                "--fullscreen" => parsed.set_window_mode(WindowMode::Fullscreen)?,
+                "--headless" => parsed.headless = true,
+                "--ticks" => parsed.ticks = Some(parse_number(&flag, &value()?)?),
+                "--replay" => parsed.replay = Some(PathBuf::from(value()?)),
+                "--data-dir" => parsed.data_dir = Some(PathBuf::from(value()?)),
+                "--lang" => {
+                    let code = value()?;
+                    parsed.language = Some(
+                        Language::from_code(&code)
+                            .ok_or_else(|| format!("unknown language {}", code))?,
+                    );
+                }
+                "--host" => parsed.host = Some(parse_host(&flag, &value()?)?),
+                "--connect" => parsed.connect = Some(value()?),
+                "--reset-highscore" => parsed.reset_highscore = true,
+                "-h" | "--help" => parsed.help = true,
+                _ => return Err(format!("unknown option {}", flag)),
+            }
+        }
+
+        if parsed.headless && parsed.ticks.is_none() {
+            return Err("--headless needs --ticks".to_string());
+        }
+        if parsed.ticks.is_some() && !parsed.headless {
+            return Err("--ticks only works with --headless".to_string());
+        }
+
+        if parsed.host.is_some() && parsed.connect.is_some() {
+            return Err("--host and --connect can't be used together".to_string());
+        }
+
+        Ok(parsed)
+    }
+
+    pub fn is_versus(&self) -> bool {
+        self.host.is_some() || self.connect.is_some()
+    }
+
+    fn set_window_mode(&mut self, window_mode: WindowMode) -> Result<(), String> {
+        match self.window_mode {
+            Some(current) if current != window_mode => {
+                Err("--windowed and --fullscreen can't be used together".to_string())
+            }
+            _ => {
+                self.window_mode = Some(window_mode);
+                Ok(())
+            }
+        }
+    }
+
+    /// Overrides the `saved` settings the arguments have something to say about, for as long
+    /// as the game runs.
+    pub fn overrides(&self, saved: Settings) -> SettingsOverrides {
+        let mut overrides = SettingsOverrides::new(saved);
+        overrides.difficulty = self.difficulty;
+        overrides.mode = self.mode;
+        overrides.window_mode = self.window_mode;
+        overrides.language = self.language;
+        // Games against another instance are classic games, one player on each side
+        if self.is_versus() {
+            overrides.mode = Some(GameMode::Classic);
+            overrides.players = Some(1);
+        }
+        overrides
+    }
+}
+
+fn parse_number(flag: &str, value: &str) -> Result<u64, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} needs a number, got {}", flag, value))
+}
+
+fn parse_host(flag: &str, value: &str) -> Result<SocketAddr, String> {
+    // A port on its own only lets in opponents from the same computer
+    if let Ok(port) = value.parse::<u16>() {
+        return Ok(SocketAddr::from(([127, 0, 0, 1], port)));
+    }
+    value
+        .parse()
+        .map_err(|_| format!("{} needs a port or an address, got {}", flag, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parses_nothing() {
+        assert_eq!(parse(&[]), Ok(Args::default()));
+    }
+
+    #[test]
+    fn parses_every_flag() {
+        let args = parse(&[
+            "--seed",
+            "42",
+            "--difficulty",
+            "hard",
+            "--mode",
+            "zen",
+            "--fullscreen",
+            "--headless",
+            "--ticks",
+            "600",
+            "--replay",
+            "run.txt",
+            "--data-dir",
+            "mods",
+            "--lang",
+            "en",
+            "--host",
+            "7777",
+            "--reset-highscore",
+            "--help",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            args,
+            Args {
+                seed: Some(42),
+                difficulty: Some(Difficulty::Hard),
+                mode: Some(GameMode::Zen),
+                window_mode: Some(WindowMode::Fullscreen),
+                headless: true,
+                ticks: Some(600),
+                replay: Some(PathBuf::from("run.txt")),
+                data_dir: Some(PathBuf::from("mods")),
+                language: Some(Language::English),
+                host: Some(SocketAddr::from(([127, 0, 0, 1], 7777))),
+                connect: None,
+                reset_highscore: true,
+                help: true,
+            }
+        );
+        assert!(args.is_versus());
+    }
+
+    #[test]
+    fn parses_the_other_flags() {
+        let args = parse(&["--windowed", "--connect", "127.0.0.1:7777", "-h"]).unwrap();
+        assert_eq!(args.window_mode, Some(WindowMode::Windowed));
+        assert_eq!(args.connect.as_deref(), Some("127.0.0.1:7777"));
+        assert!(args.help);
+        assert!(args.is_versus());
+    }
+
+    #[test]
+    fn parses_inline_values() {
+        let args = parse(&[
+            "--seed=7",
+            "--difficulty=easy",
+            "--mode=time-attack-60",
+            "--lang=ro",
+            "--host=80",
+        ])
+        .unwrap();
+        assert_eq!(args.seed, Some(7));
+        assert_eq!(args.difficulty, Some(Difficulty::Easy));
+        assert_eq!(args.mode, Some(GameMode::TimeAttack60));
+        assert_eq!(args.language, Some(Language::Romanian));
+        assert_eq!(args.host, Some(SocketAddr::from(([127, 0, 0, 1], 80))));
+
+        // Only the first `=` splits, the rest belongs to the value
+        let args = parse(&["--data-dir=a=b"]).unwrap();
+        assert_eq!(args.data_dir, Some(PathBuf::from("a=b")));
+    }
+
+    #[test]
+    fn hosts_on_other_addresses() {
+        let args = parse(&["--host", "0.0.0.0:7777"]).unwrap();
+        assert_eq!(args.host, Some(SocketAddr::from(([0, 0, 0, 0], 7777))));
+        assert!(parse(&["--host", "localhost"]).is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_flags() {
+        assert!(parse(&["--speed", "2"]).is_err());
+        assert!(parse(&["seed"]).is_err());
+        assert!(parse(&["--seed=1=2"]).is_err());
+    }
+
+    #[test]
+    fn rejects_missing_values() {
+        for flag in &[
+            "--seed",
+            "--difficulty",
+            "--mode",
+            "--replay",
+            "--data-dir",
+            "--lang",
+            "--host",
+            "--connect",
+        ] {
+            assert!(parse(&[*flag]).is_err(), "{} without a value", flag);
+        }
+        assert!(parse(&["--headless", "--ticks"]).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        assert!(parse(&["--seed", "many"]).is_err());
+        assert!(parse(&["--seed", "-1"]).is_err());
+        assert!(parse(&["--headless", "--ticks", "1.5"]).is_err());
+        assert!(parse(&["--host", "99999"]).is_err());
+        assert!(parse(&["--difficulty", "impossible"]).is_err());
+        assert!(parse(&["--mode", "time-attack"]).is_err());
+        assert!(parse(&["--lang", "fr"]).is_err());
+    }
+
+    #[test]
+    fn ticks_need_headless() {
+        assert!(parse(&["--ticks", "60"]).is_err());
+        assert!(parse(&["--headless"]).is_err());
+        assert!(parse(&["--headless", "--ticks", "60"]).is_ok());
+    }
+
+    #[test]
+    fn rejects_conflicting_flags() {
+        assert!(parse(&["--host", "7777", "--connect", "127.0.0.1:7777"]).is_err());
+        assert!(parse(&["--windowed", "--fullscreen"]).is_err());
+        assert!(parse(&["--fullscreen", "--fullscreen"]).is_ok());
+    }
+}
@@ -0,0 +1,200 @@
+use bevy::{
+    asset::{AssetIo, AssetIoError, FileAssetIo},
+    prelude::*,
+    tasks::IoTaskPool,
+    utils::BoxedFuture,
+};
+use std::{
+    borrow::Cow,
+    fs,
+    path::{Path, PathBuf},
+};
+
+macro_rules! embed {
+    ($($path:literal),* $(,)?) => {
+        &[$(($path, include_bytes!(concat!("../assets/", $path)) as &[u8])),*]
+    };
+}
+
+/// Everything the game needs to run, so that the executable can be shipped on its own. The
+/// bundled font is missing from here because the font plugin embeds it by itself.
+static EMBEDDED: &[(&str, &[u8])] = embed![
+    "achievements.toml",
+    "arenas/classic.toml",
+    "arenas/platforms.toml",
+    "backgroun2d.png",
+    "cloud.png",
+    "heart.png",
+    "high-score.png",
+    "levels/campaign.toml",
+    "locales/en.ftl",
+    "locales/ro.ftl",
+    "objects/heart.toml",
+    "objects/star.toml",
+    "pause.png",
+    "play.png",
+    "star.png",
+    "themes/day.toml",
+    "themes/night.toml",
+    "themes/night/background.png",
+    "themes/night/cloud.png",
+    "themes/night/heart.png",
+    "themes/night/star.png",
+    "themes/space.toml",
+    "themes/space/background.png",
+    "themes/space/cloud.png",
+    "themes/space/heart.png",
+    "themes/space/star.png",
+    "themes/underwater.toml",
+    "themes/underwater/background.png",
+    "themes/underwater/cloud.png",
+    "themes/underwater/heart.png",
+    "themes/underwater/star.png",
+];
+
+/// Where the game's files come from. Files in the override directory take precedence over
+/// the embedded ones, which lets mods replace or add assets without rebuilding the game.
+#[derive(Clone)]
+pub struct AssetSource {
+    override_dir: Option<PathBuf>,
+}
+
+impl AssetSource {
+    pub fn new(override_dir: Option<PathBuf>) -> Self {
+        Self { override_dir }
+    }
+
+    /// Uses the `assets` folder next to the game as the override directory, if there is one.
+    pub fn with_default_override() -> Self {
+        let assets = FileAssetIo::get_root_path().join("assets");
+        Self::new(if assets.is_dir() { Some(assets) } else { None })
+    }
+
+    fn on_disk(&self, path: &Path) -> Option<PathBuf> {
+        self.override_dir
+            .as_ref()
+            .map(|dir| dir.join(path))
+            .filter(|path| path.is_file())
+    }
+
+    fn embedded(path: &Path) -> Option<&'static [u8]> {
+        EMBEDDED
+            .iter()
+            .find(|(embedded_path, _)| Path::new(embedded_path) == path)
+            .map(|(_, bytes)| *bytes)
+    }
+
+    pub fn exists(&self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        self.on_disk(path).is_some() || Self::embedded(path).is_some()
+    }
+
+    pub fn read(&self, path: impl AsRef<Path>) -> Option<Cow<'static, [u8]>> {
+        let path = path.as_ref();
+        match self.on_disk(path) {
+            Some(disk_path) => fs::read(disk_path).ok().map(Cow::Owned),
+            None => Self::embedded(path).map(Cow::Borrowed),
+        }
+    }
+
+    pub fn read_to_string(&self, path: impl AsRef<Path>) -> Option<String> {
+        self.read(path)
+            .and_then(|bytes| String::from_utf8(bytes.into_owned()).ok())
+    }
+
+    /// The paths of everything directly in `dir`, whether embedded or in the override
+    /// directory, sorted.
+    pub fn read_dir(&self, dir: impl AsRef<Path>) -> Vec<PathBuf> {
+        let dir = dir.as_ref();
+        let mut entries: Vec<PathBuf> = EMBEDDED
+            .iter()
+            .map(|(embedded_path, _)| PathBuf::from(embedded_path))
+            .filter(|embedded_path| embedded_path.parent() == Some(dir))
+            .collect();
+
+        if let Some(override_dir) = &self.override_dir {
+            if let Ok(read_dir) = fs::read_dir(override_dir.join(dir)) {
+                for entry in read_dir.flatten() {
+                    let entry = dir.join(entry.file_name());
+                    if !entries.contains(&entry) {
+                        entries.push(entry);
+                    }
+                }
+            }
+        }
+
+        entries.sort();
+        entries
+    }
+}
+
+/// Makes Bevy load assets through [`AssetSource`]. Has to be added before
+/// [`bevy::asset::AssetPlugin`].
+pub struct EmbeddedAssetPlugin;
+
+impl Plugin for EmbeddedAssetPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        if app.world().get_resource::<AssetSource>().is_none() {
+            app.insert_resource(AssetSource::with_default_override());
+        }
+
+        let source = app.world().get_resource::<AssetSource>().unwrap().clone();
+        let task_pool = app
+            .world()
+            .get_resource::<IoTaskPool>()
+            .expect("the core plugin should be added before the embedded asset plugin")
+            .0
+            .clone();
+
+        app.insert_resource(AssetServer::new(EmbeddedAssetIo { source }, task_pool));
+    }
+}
+
+struct EmbeddedAssetIo {
+    source: AssetSource,
+}
+
+impl AssetIo for EmbeddedAssetIo {
+    fn load_path<'a>(&'a self, path: &'a Path) -> BoxedFuture<'a, Result<Vec<u8>, AssetIoError>> {
+        Box::pin(async move {
+            self.source
+                .read(path)
+                .map(Cow::into_owned)
+                .ok_or_else(|| AssetIoError::NotFound(path.to_path_buf()))
+        })
+    }
+
+    fn read_directory(
+        &self,
+        path: &Path,
+    ) -> Result<Box<dyn Iterator<Item = PathBuf>>, AssetIoError> {
+        let entries = self.source.read_dir(path);
+        if entries.is_empty() && !self.is_directory(path) {
+            return Err(AssetIoError::NotFound(path.to_path_buf()));
+        }
+
+        Ok(Box::new(entries.into_iter()))
+    }
+
+    fn is_directory(&self, path: &Path) -> bool {
+        let on_disk = self
+            .source
+            .override_dir
+            .as_ref()
+            .map_or(false, |dir| dir.join(path).is_dir());
+
+        on_disk
+            || EMBEDDED.iter().any(|(embedded_path, _)| {
+                let embedded_path = Path::new(embedded_path);
+                embedded_path != path && embedded_path.starts_with(path)
+            })
+    }
+
+    fn watch_path_for_changes(&self, _path: &Path) -> Result<(), AssetIoError> {
+        Ok(())
+    }
+
+    fn watch_for_changes(&self) -> Result<(), AssetIoError> {
+        Ok(())
+    }
+}
@@ -0,0 +1,79 @@
+use crate::embedded::AssetSource;
+use bevy::{asset::LoadState, prelude::*};
+
+static BUNDLED_FONT: &[u8] = include_bytes!("../assets/fonts/DejaVuSansMono.ttf");
+static DEFAULT_OVERRIDE: &str = "FiraCode-Regular.ttf";
+
+/// Provides the font used by all of the game's text.
+///
+/// A font bundled in the executable is always available. Users can still drop another font in
+/// the asset override folder and point [`FontOverride`] to it, by default Fira Code is picked
+/// up when present. Should the override be missing or fail to load, the bundled font is used
+/// instead and the problem is logged.
+pub struct FontPlugin;
+
+impl Plugin for FontPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        if app.world().get_resource::<FontOverride>().is_none() {
+            app.insert_resource(FontOverride(Some(DEFAULT_OVERRIDE.to_string())));
+        }
+
+        let world = app.world_mut();
+        let bundled = world
+            .get_resource_mut::<Assets<Font>>()
+            .expect("the text plugin should be added before the font plugin")
+            .add(Font::try_from_bytes(BUNDLED_FONT.to_vec()).expect("the bundled font is valid"));
+
+        let font_override = world.get_resource::<FontOverride>().unwrap().0.clone();
+        let ui = match font_override {
+            Some(path) if world.get_resource::<AssetSource>().unwrap().exists(&path) => {
+                info!("Using {} as the UI font", path);
+                world
+                    .get_resource::<AssetServer>()
+                    .unwrap()
+                    .load(path.as_str())
+            }
+            Some(path) => {
+                // Only worth a warning when someone asked for a different font on purpose
+                if path != DEFAULT_OVERRIDE {
+                    warn!("Font {} does not exist, using the bundled font", path);
+                }
+                bundled.clone()
+            }
+            None => bundled.clone(),
+        };
+
+        app.insert_resource(Fonts { ui, bundled });
+        app.add_system(fall_back_on_failed_font.system());
+    }
+}
+
+/// A font from the `assets` folder to use instead of the bundled one.
+pub struct FontOverride(pub Option<String>);
+
+pub struct Fonts {
+    pub ui: Handle<Font>,
+    bundled: Handle<Font>,
+}
+
+fn fall_back_on_failed_font(
+    asset_server: Res<AssetServer>,
+    mut fonts: ResMut<Fonts>,
+    mut texts: Query<&mut Text>,
+) {
+    if fonts.ui == fonts.bundled || asset_server.get_load_state(&fonts.ui) != LoadState::Failed {
+        return;
+    }
+
+    error!("The UI font failed to load, falling back to the bundled font");
+
+    let bundled = fonts.bundled.clone();
+    let failed = std::mem::replace(&mut fonts.ui, bundled);
+    for mut text in texts.iter_mut() {
+        for section in text.sections.iter_mut() {
+            if section.style.font == failed {
+                section.style.font = fonts.ui.clone();
+            }
+        }
+    }
+}
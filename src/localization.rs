@@ -0,0 +1,209 @@
+use crate::{
+    embedded::AssetSource,
+    settings::{Settings, SettingsScreen},
+};
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Loads the UI text from `assets/locales/<language>.ftl` and keeps everything marked with
+/// [`Localized`] in sync with the current language.
+pub struct LocalizationPlugin;
+
+impl Plugin for LocalizationPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        if app.world().get_resource::<Localization>().is_none() {
+            let world = app.world();
+            let language = world.get_resource::<Settings>().map_or_else(
+                || Settings::default().language,
+                |settings| settings.language,
+            );
+            let source = world
+                .get_resource::<AssetSource>()
+                .expect("the embedded asset plugin should be added before the localization plugin");
+            let localization = Localization::load(source, language);
+            app.insert_resource(localization);
+        }
+        app.add_system(switch_language.system());
+        app.add_system(localize_texts.system());
+        app.add_system(localize_window_title.system());
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Language {
+    #[serde(rename = "en")]
+    English,
+    #[serde(rename = "ro")]
+    Romanian,
+}
+
+impl Language {
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::English => "en",
+            Self::Romanian => "ro",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code.trim() {
+            "en" => Some(Self::English),
+            "ro" => Some(Self::Romanian),
+            _ => None,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Self::English => Self::Romanian,
+            Self::Romanian => Self::English,
+        }
+    }
+}
+
+/// The messages of one language, written in a small subset of the
+/// [Fluent](https://projectfluent.org) syntax: `key = value` pairs, values continued on
+/// indented lines, `#` comments and `{ $variable }` placeables.
+pub struct Localization {
+    language: Language,
+    messages: HashMap<String, String>,
+}
+
+impl Localization {
+    pub fn load(source: &AssetSource, language: Language) -> Self {
+        let path = PathBuf::from("locales")
+            .join(language.code())
+            .with_extension("ftl");
+
+        let messages = match source.read_to_string(&path) {
+            Some(contents) => parse_messages(&contents),
+            None => {
+                warn!("Could not load {}", path.display());
+                HashMap::default()
+            }
+        };
+
+        Self { language, messages }
+    }
+
+    pub fn language(&self) -> Language {
+        self.language
+    }
+
+    /// Looks up the message called `key`. Missing messages show up as their key, so that
+    /// they are easy to spot.
+    pub fn get(&self, key: &str) -> String {
+        self.format(key, &[])
+    }
+
+    /// Looks up the message called `key` and fills in its placeables from `args`.
+    pub fn format(&self, key: &str, args: &[(&str, String)]) -> String {
+        let message = match self.messages.get(key) {
+            Some(message) => message,
+            None => return key.to_string(),
+        };
+
+        let mut formatted = message.clone();
+        for (name, value) in args {
+            formatted = formatted.replace(&format!("{{ ${} }}", name), value);
+        }
+        formatted
+    }
+}
+
+fn parse_messages(source: &str) -> HashMap<String, String> {
+    let mut messages = HashMap::default();
+    let mut current: Option<(String, Vec<String>)> = None;
+
+    for line in source.lines() {
+        let is_continuation = line.starts_with(' ') || line.starts_with('\t');
+
+        if is_continuation && !line.trim().is_empty() {
+            if let Some((_, lines)) = current.as_mut() {
+                lines.push(line.trim().to_string());
+            }
+            continue;
+        }
+
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some((key, lines)) = current.take() {
+            messages.insert(key, lines.join("\n"));
+        }
+
+        if let Some((key, value)) = line.split_once('=') {
+            let value = value.trim();
+            let lines = if value.is_empty() {
+                Vec::new()
+            } else {
+                vec![value.to_string()]
+            };
+            current = Some((key.trim().to_string(), lines));
+        }
+    }
+
+    if let Some((key, lines)) = current.take() {
+        messages.insert(key, lines.join("\n"));
+    }
+
+    messages
+}
+
+/// Marks a text whose first section should always show the message called `0`. The keys
+/// bound to each control can be used in it as `{ $left }`, `{ $right }`, `{ $pause }`,
+/// `{ $start }` and `{ $language }`.
+pub struct Localized(pub &'static str);
+
+/// The message used as the window title.
+pub struct WindowTitle(pub &'static str);
+
+/// The new language gets loaded, and saved, by the settings plugin.
+fn switch_language(
+    keyboard_input: Res<Input<KeyCode>>,
+    screen: Res<SettingsScreen>,
+    mut settings: ResMut<Settings>,
+) {
+    // The key might be getting bound to a control
+    if keyboard_input.just_pressed(settings.controls.language.0) && !screen.is_open() {
+        settings.language = settings.language.next();
+    }
+}
+
+fn localize_texts(
+    localization: Res<Localization>,
+    settings: Res<Settings>,
+    mut query: Query<(&Localized, ChangeTrackers<Localized>, &mut Text)>,
+) {
+    // Texts may mention the controls, which can be changed at any time
+    let controls = settings.controls;
+    let args = [
+        ("left", controls.left.name().to_string()),
+        ("right", controls.right.name().to_string()),
+        ("pause", controls.pause.name().to_string()),
+        ("start", controls.start.name().to_string()),
+        ("language", controls.language.name().to_string()),
+    ];
+
+    for (localized, tracker, mut text) in query.iter_mut() {
+        if localization.is_changed() || settings.is_changed() || tracker.is_changed() {
+            text.sections[0].value = localization.format(localized.0, &args);
+        }
+    }
+}
+
+fn localize_window_title(
+    localization: Res<Localization>,
+    title: Res<WindowTitle>,
+    mut windows: ResMut<Windows>,
+) {
+    if !localization.is_changed() {
+        return;
+    }
+
+    if let Some(window) = windows.get_primary_mut() {
+        window.set_title(localization.get(title.0));
+    }
+}
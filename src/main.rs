@@ -1,28 +1,162 @@
+mod cli;
+mod embedded;
+mod fonts;
+mod localization;
+mod settings;
+mod storage;
 mod world;
 
-use bevy::prelude::*;
+use bevy::{
+    asset::AssetPlugin, audio::AudioPlugin, gilrs::GilrsPlugin, prelude::*, wgpu::WgpuPlugin,
+    winit::WinitPlugin,
+};
+use cli::{Args, USAGE};
+use embedded::{AssetSource, EmbeddedAssetPlugin};
+use fonts::{FontOverride, FontPlugin};
+use localization::{Localization, LocalizationPlugin, WindowTitle};
 use rand::{thread_rng, Rng};
+use settings::{Settings, SettingsPlugin};
+use std::{process, thread, time::Duration};
+use world::{HighScores, Player, Replay, Scoreboard, Seed, Versus};
 
 static TITLES: [&str; 3] = [
-    "Starhigh! Colectează cât mai multe stele!",
-    "Starhigh! Dacă nu prinzi o stea, pierzi o viață.",
-    "Starhigh! Dacă nu prinzi o inimă, pierzi un punct.",
+    "window-title-stars",
+    "window-title-lives",
+    "window-title-hearts",
 ];
 
 fn main() {
-    let title_index = thread_rng().gen_range(0..TITLES.len());
-
-    App::build()
-        .insert_resource(WindowDescriptor {
-            title: TITLES[title_index].to_string(),
-            vsync: true,
-            width: 540.0,
-            height: 600.0,
-            resizable: false,
-            ..Default::default()
-        })
-        .add_plugins(DefaultPlugins)
-        .add_plugin(world::World)
-        .run();
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, USAGE);
+            process::exit(2);
+        }
+    };
+    if args.help {
+        println!("{}", USAGE);
+        return;
+    }
+    if args.reset_highscore {
+        HighScores::default().update_file();
+    }
+
+    let replay = args.replay.as_ref().map(|path| match Replay::load(path) {
+        Ok(replay) => replay,
+        Err(error) => {
+            eprintln!("Could not load the replay {}: {}", path.display(), error);
+            process::exit(1);
+        }
+    });
+
+    let title = WindowTitle(TITLES[thread_rng().gen_range(0..TITLES.len())]);
+    let asset_source = match &args.data_dir {
+        Some(dir) => AssetSource::new(Some(dir.clone())),
+        None => AssetSource::with_default_override(),
+    };
+    let mut overrides = args.overrides(Settings::load_from_file());
+    if let Some(replay) = &replay {
+        replay.apply(&mut overrides);
+    }
+    let mut settings = overrides.apply();
+    let localization = Localization::load(&asset_source, settings.language);
+
+    // A replay only plays out the same way with the seed it was recorded with
+    let seed = match (&replay, args.seed) {
+        (Some(replay), _) => replay.seed,
+        (None, Some(seed)) => seed,
+        (None, None) => thread_rng().gen(),
+    };
+    // The host picks the run both sides play
+    let versus = match (args.host, &args.connect) {
+        (Some(address), _) => Some(
+            Versus::host(address, seed, settings.difficulty)
+                .map(|versus| (versus, seed, settings.difficulty)),
+        ),
+        (None, Some(address)) => Some(Versus::join(address)),
+        (None, None) => None,
+    };
+    let (versus, seed) = match versus {
+        Some(Ok((versus, seed, difficulty))) => {
+            overrides.difficulty = Some(difficulty);
+            settings.difficulty = difficulty;
+            (Some(versus), seed)
+        }
+        Some(Err(error)) => {
+            eprintln!("Could not start a game against another player: {}", error);
+            process::exit(1);
+        }
+        None => (None, seed),
+    };
+
+    // Nobody is there to press start when running headless
+    let replay = match replay {
+        None if args.headless => Some(Replay::autostart(seed, settings.controls.start)),
+        replay => replay,
+    };
+
+    let mut app = App::build();
+    app.insert_resource(WindowDescriptor {
+        title: localization.get(title.0),
+        vsync: settings.vsync,
+        width: 540.0,
+        height: 600.0,
+        resizable: true,
+        mode: settings.window_mode.to_bevy(),
+        ..Default::default()
+    })
+    .insert_resource(title)
+    .insert_resource(localization)
+    .insert_resource(asset_source)
+    .insert_resource(FontOverride(settings.font.clone()))
+    .insert_resource(settings)
+    .insert_resource(overrides)
+    .insert_resource(Seed(seed));
+    if let Some(replay) = replay {
+        app.insert_resource(replay);
+    }
+    if let Some(versus) = versus {
+        app.insert_resource(versus);
+    }
+
+    let headless = args.headless;
+    app.add_plugins_with(DefaultPlugins, |group| {
+        group.add_before::<AssetPlugin, _>(EmbeddedAssetPlugin);
+        if headless {
+            group
+                .disable::<WinitPlugin>()
+                .disable::<WgpuPlugin>()
+                .disable::<AudioPlugin>()
+                .disable::<GilrsPlugin>();
+        }
+        group
+    })
+    .add_plugin(FontPlugin)
+    .add_plugin(LocalizationPlugin)
+    .add_plugin(SettingsPlugin)
+    .add_plugin(world::World);
+
+    if let Some(ticks) = args.ticks {
+        app.set_runner(move |app| run_headless(app, ticks));
+    }
+    app.run();
     println!("Hello, world!");
 }
+
+/// Runs `ticks` frames, then prints the score of every player. The game counts every frame
+/// as 1/60 of a second however long it takes, waiting only keeps an opponent in step.
+fn run_headless(mut app: App, ticks: u64) {
+    for _ in 0..ticks {
+        app.update();
+        thread::sleep(Duration::from_secs_f64(1.0 / 60.0));
+    }
+
+    let mut players = app.world.query::<(&Player, &Scoreboard)>();
+    let mut scores: Vec<_> = players
+        .iter(&app.world)
+        .map(|(player, scoreboard)| (player.0, scoreboard.score()))
+        .collect();
+    scores.sort_unstable();
+    let scores: Vec<_> = scores.iter().map(|(_, score)| score.to_string()).collect();
+    println!("{}", scores.join(" "));
+}
@@ -0,0 +1,502 @@
+mod screen;
+
+pub use screen::SettingsScreen;
+
+use crate::{
+    embedded::AssetSource,
+    localization::{Language, Localization},
+    storage,
+};
+use bevy::{prelude::*, window::WindowMode as BevyWindowMode};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Keeps [`Settings`] applied to the game and saved to disk, and provides the settings screen.
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        if app.world().get_resource::<Settings>().is_none() {
+            app.insert_resource(Settings::load_from_file());
+        }
+        if app.world().get_resource::<SettingsOverrides>().is_none() {
+            let settings = app.world().get_resource::<Settings>().unwrap().clone();
+            app.insert_resource(SettingsOverrides::new(settings));
+        }
+        app.add_system(apply_settings.system());
+        app.add_plugin(screen::SettingsScreenPlugin);
+    }
+}
+
+/// Everything the player can configure. Stored as `settings.toml` in the user's config
+/// directory, values missing from the file keep their defaults.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub language: Language,
+    pub difficulty: Difficulty,
+    /// The mode new games are played in
+    pub mode: GameMode,
+    /// How many players share the arena, 1 or 2
+    pub players: usize,
+    /// Whether the cloud can't lose another life for a moment after losing one
+    pub grace_period: bool,
+    /// Hearts caught past this many lives are lost
+    pub max_lives: u64,
+    pub window_mode: WindowMode,
+    pub vsync: bool,
+    pub effects: VisualEffects,
+    pub controls: Controls,
+    /// The arena layout, see `assets/arenas`
+    pub arena: String,
+    /// The look of the game, see `assets/themes`
+    pub theme: String,
+    /// A font from the assets folder to use instead of the bundled one
+    pub font: Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            language: Language::Romanian,
+            difficulty: Difficulty::Normal,
+            mode: GameMode::Classic,
+            players: 1,
+            grace_period: true,
+            max_lives: 5,
+            window_mode: WindowMode::Windowed,
+            vsync: true,
+            effects: VisualEffects::default(),
+            controls: Controls::default(),
+            arena: "classic".to_string(),
+            theme: "day".to_string(),
+            font: Some("FiraCode-Regular.ttf".to_string()),
+        }
+    }
+}
+
+impl Settings {
+    pub fn load_from_file() -> Self {
+        storage::load_toml(storage::config_file("settings.toml"), "settings")
+    }
+
+    pub fn update_file(&self) {
+        storage::save_toml(storage::config_file("settings.toml"), "settings", self);
+    }
+}
+
+/// Settings given on the command line, which only last until the game is closed. The
+/// [`Settings`] resource holds them, but they never make it to the file: the values they
+/// replaced are saved instead, until the player changes the setting themselves.
+pub struct SettingsOverrides {
+    pub difficulty: Option<Difficulty>,
+    pub window_mode: Option<WindowMode>,
+    pub language: Option<Language>,
+    pub mode: Option<GameMode>,
+    pub players: Option<usize>,
+    pub grace_period: Option<bool>,
+    pub max_lives: Option<u64>,
+    pub arena: Option<String>,
+    /// What the settings file holds
+    saved: Settings,
+}
+
+impl SettingsOverrides {
+    pub fn new(saved: Settings) -> Self {
+        Self {
+            difficulty: None,
+            window_mode: None,
+            language: None,
+            mode: None,
+            players: None,
+            grace_period: None,
+            max_lives: None,
+            arena: None,
+            saved,
+        }
+    }
+
+    /// The saved settings, overridden.
+    pub fn apply(&self) -> Settings {
+        let mut settings = self.saved.clone();
+        settings.difficulty = self.difficulty.unwrap_or(settings.difficulty);
+        settings.window_mode = self.window_mode.unwrap_or(settings.window_mode);
+        settings.language = self.language.unwrap_or(settings.language);
+        settings.mode = self.mode.unwrap_or(settings.mode);
+        settings.players = self.players.unwrap_or(settings.players);
+        settings.grace_period = self.grace_period.unwrap_or(settings.grace_period);
+        settings.max_lives = self.max_lives.unwrap_or(settings.max_lives);
+        if let Some(arena) = &self.arena {
+            settings.arena = arena.clone();
+        }
+        settings
+    }
+
+    /// What should be saved now that the settings changed. Overrides the player changed since
+    /// are dropped, the new values are theirs to keep.
+    fn save(&mut self, settings: &Settings) -> &Settings {
+        let previous = std::mem::replace(&mut self.saved, settings.clone());
+        let saved = &mut self.saved;
+        restore(
+            &mut self.difficulty,
+            &mut saved.difficulty,
+            previous.difficulty,
+        );
+        restore(
+            &mut self.window_mode,
+            &mut saved.window_mode,
+            previous.window_mode,
+        );
+        restore(&mut self.language, &mut saved.language, previous.language);
+        restore(&mut self.mode, &mut saved.mode, previous.mode);
+        restore(&mut self.players, &mut saved.players, previous.players);
+        restore(
+            &mut self.grace_period,
+            &mut saved.grace_period,
+            previous.grace_period,
+        );
+        restore(
+            &mut self.max_lives,
+            &mut saved.max_lives,
+            previous.max_lives,
+        );
+        restore(&mut self.arena, &mut saved.arena, previous.arena);
+        &self.saved
+    }
+}
+
+/// Puts back the `previous` value while the override still holds, or drops the override.
+fn restore<T: PartialEq>(overridden: &mut Option<T>, value: &mut T, previous: T) {
+    if overridden.as_ref() == Some(&*value) {
+        *value = previous;
+    } else {
+        *overridden = None;
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Easy => "easy",
+            Self::Normal => "normal",
+            Self::Hard => "hard",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "easy" => Some(Self::Easy),
+            "normal" => Some(Self::Normal),
+            "hard" => Some(Self::Hard),
+            _ => None,
+        }
+    }
+
+    /// How fast objects fall at the start of a game.
+    pub fn initial_speed(self) -> f32 {
+        match self {
+            Self::Easy => 0.75,
+            Self::Normal => 1.0,
+            Self::Hard => 1.5,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum GameMode {
+    /// Play until every life is lost
+    #[serde(rename = "classic")]
+    Classic,
+    /// Catch as many stars as possible before the time runs out, lives don't run out
+    #[serde(rename = "time-attack-60")]
+    TimeAttack60,
+    #[serde(rename = "time-attack-120")]
+    TimeAttack120,
+    /// Practice without losing lives, the speed is set by hand
+    #[serde(rename = "zen")]
+    Zen,
+    /// A classic game everyone plays with the same seed on the same day, scored only once
+    #[serde(rename = "daily")]
+    Daily,
+    /// Levels with their own goals, see `assets/levels/campaign.toml`
+    #[serde(rename = "campaign")]
+    Campaign,
+}
+
+pub static GAME_MODES: [GameMode; 6] = [
+    GameMode::Classic,
+    GameMode::TimeAttack60,
+    GameMode::TimeAttack120,
+    GameMode::Zen,
+    GameMode::Daily,
+    GameMode::Campaign,
+];
+
+impl GameMode {
+    /// The name used in save files and messages, the same one used in `settings.toml`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Classic => "classic",
+            Self::TimeAttack60 => "time-attack-60",
+            Self::TimeAttack120 => "time-attack-120",
+            Self::Zen => "zen",
+            Self::Daily => "daily",
+            Self::Campaign => "campaign",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        GAME_MODES.iter().copied().find(|mode| mode.name() == name)
+    }
+
+    /// How many seconds a game lasts, if it's not over when the player runs out of lives.
+    pub fn time_limit(self) -> Option<f32> {
+        match self {
+            Self::Classic | Self::Zen | Self::Daily | Self::Campaign => None,
+            Self::TimeAttack60 => Some(60.0),
+            Self::TimeAttack120 => Some(120.0),
+        }
+    }
+
+    pub fn has_lives(self) -> bool {
+        matches!(self, Self::Classic | Self::Daily | Self::Campaign)
+    }
+
+    /// Practice games don't set high scores.
+    pub fn is_practice(self) -> bool {
+        self == Self::Zen
+    }
+
+    /// Whether the speed only changes when asked to.
+    pub fn has_manual_speed(self) -> bool {
+        self == Self::Zen
+    }
+
+    /// Whether score milestones set off meteor showers, storms and nights. Campaign levels are
+    /// built by hand instead.
+    pub fn has_scripted_events(self) -> bool {
+        !matches!(self, Self::Zen | Self::Campaign)
+    }
+
+    /// The difficulty the mode is always played on, rather than the one in the settings.
+    pub fn difficulty(self) -> Option<Difficulty> {
+        match self {
+            Self::Daily => Some(Difficulty::Normal),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowMode {
+    Windowed,
+    Fullscreen,
+}
+
+impl WindowMode {
+    pub fn to_bevy(self) -> BevyWindowMode {
+        match self {
+            Self::Windowed => BevyWindowMode::Windowed,
+            Self::Fullscreen => BevyWindowMode::BorderlessFullscreen,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VisualEffects {
+    pub background: bool,
+    pub letterbox: bool,
+}
+
+impl Default for VisualEffects {
+    fn default() -> Self {
+        Self {
+            background: true,
+            letterbox: true,
+        }
+    }
+}
+
+/// The keys used to play. The arrow keys move the cloud as well, unless they control the
+/// second player's.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Controls {
+    pub left: Key,
+    pub right: Key,
+    pub pause: Key,
+    pub start: Key,
+    /// Switches to the next language, whenever the settings screen is closed
+    pub language: Key,
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        Self {
+            left: Key(KeyCode::A),
+            right: Key(KeyCode::D),
+            pause: Key(KeyCode::P),
+            start: Key(KeyCode::S),
+            language: Key(KeyCode::L),
+        }
+    }
+}
+
+/// A key which can be written to the settings file, or a replay, by name.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Key(pub KeyCode);
+
+/// Every key the game reads has to be here, or replays would leave it out.
+static KEY_NAMES: &[(KeyCode, &str)] = &[
+    (KeyCode::A, "A"),
+    (KeyCode::B, "B"),
+    (KeyCode::C, "C"),
+    (KeyCode::D, "D"),
+    (KeyCode::E, "E"),
+    (KeyCode::F, "F"),
+    (KeyCode::G, "G"),
+    (KeyCode::H, "H"),
+    (KeyCode::I, "I"),
+    (KeyCode::J, "J"),
+    (KeyCode::K, "K"),
+    (KeyCode::L, "L"),
+    (KeyCode::M, "M"),
+    (KeyCode::N, "N"),
+    (KeyCode::O, "O"),
+    (KeyCode::P, "P"),
+    (KeyCode::Q, "Q"),
+    (KeyCode::R, "R"),
+    (KeyCode::S, "S"),
+    (KeyCode::T, "T"),
+    (KeyCode::U, "U"),
+    (KeyCode::V, "V"),
+    (KeyCode::W, "W"),
+    (KeyCode::X, "X"),
+    (KeyCode::Y, "Y"),
+    (KeyCode::Z, "Z"),
+    (KeyCode::Key0, "0"),
+    (KeyCode::Key1, "1"),
+    (KeyCode::Key2, "2"),
+    (KeyCode::Key3, "3"),
+    (KeyCode::Key4, "4"),
+    (KeyCode::Key5, "5"),
+    (KeyCode::Key6, "6"),
+    (KeyCode::Key7, "7"),
+    (KeyCode::Key8, "8"),
+    (KeyCode::Key9, "9"),
+    (KeyCode::Left, "Left"),
+    (KeyCode::Right, "Right"),
+    (KeyCode::Up, "Up"),
+    (KeyCode::Down, "Down"),
+    (KeyCode::Space, "Space"),
+    (KeyCode::Return, "Enter"),
+    (KeyCode::Tab, "Tab"),
+    (KeyCode::LShift, "LeftShift"),
+    (KeyCode::RShift, "RightShift"),
+    (KeyCode::LControl, "LeftControl"),
+    (KeyCode::RControl, "RightControl"),
+    (KeyCode::Escape, "Escape"),
+    (KeyCode::F2, "F2"),
+    (KeyCode::F3, "F3"),
+    (KeyCode::F11, "F11"),
+    (KeyCode::Equals, "Equals"),
+    (KeyCode::Plus, "Plus"),
+    (KeyCode::Minus, "Minus"),
+    (KeyCode::NumpadAdd, "NumpadAdd"),
+    (KeyCode::NumpadSubtract, "NumpadSubtract"),
+];
+
+impl Key {
+    pub fn from_key_code(key_code: KeyCode) -> Option<Self> {
+        KEY_NAMES
+            .iter()
+            .find(|(code, _)| *code == key_code)
+            .map(|_| Self(key_code))
+    }
+
+    pub fn name(self) -> &'static str {
+        KEY_NAMES
+            .iter()
+            .find(|(code, _)| *code == self.0)
+            .map_or("?", |(_, name)| *name)
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        KEY_NAMES
+            .iter()
+            .find(|(_, key_name)| key_name.eq_ignore_ascii_case(name))
+            .map(|(code, _)| Self(*code))
+    }
+}
+
+impl Serialize for Key {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Self::from_name(&name)
+            .ok_or_else(|| de::Error::custom(format!("unsupported key {:?}", name)))
+    }
+}
+
+/// Marks the background sprite, which can be turned off in the settings.
+pub struct Background;
+
+/// Marks the bars hiding everything outside of the arena, which can be turned off in the
+/// settings.
+pub struct Letterbox;
+
+fn apply_settings(
+    settings: Res<Settings>,
+    mut overrides: ResMut<SettingsOverrides>,
+    source: Res<AssetSource>,
+    mut localization: ResMut<Localization>,
+    mut windows: ResMut<Windows>,
+    mut visibles: QuerySet<(
+        Query<&mut Visible, With<Background>>,
+        Query<&mut Visible, With<Letterbox>>,
+    )>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    if localization.language() != settings.language {
+        *localization = Localization::load(&source, settings.language);
+    }
+
+    if let Some(window) = windows.get_primary_mut() {
+        let mode = settings.window_mode.to_bevy();
+        if window.mode() != mode {
+            window.set_mode(mode);
+        }
+        if window.vsync() != settings.vsync {
+            window.set_vsync(settings.vsync);
+        }
+    }
+
+    for mut visible in visibles.q0_mut().iter_mut() {
+        visible.is_visible = settings.effects.background;
+    }
+    for mut visible in visibles.q1_mut().iter_mut() {
+        visible.is_visible = settings.effects.letterbox;
+    }
+
+    // The first run only sees the settings being added, there's nothing new to save
+    if !settings.is_added() {
+        overrides.save(&settings).update_file();
+    }
+}
@@ -0,0 +1,348 @@
+use bevy::prelude::*;
+
+use super::{Difficulty, Key, Settings, WindowMode, GAME_MODES};
+use crate::{
+    fonts::Fonts,
+    localization::{Language, Localization},
+    world::{ArenaViewport, HighScores, HudAnchor, Themes},
+};
+
+static NAVIGATE_SETTINGS_LABEL: &str = "navigate_settings_system";
+
+pub(super) struct SettingsScreenPlugin;
+
+impl Plugin for SettingsScreenPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(SettingsScreen::default());
+        app.add_system(navigate_settings.system().label(NAVIGATE_SETTINGS_LABEL));
+        app.add_system(render_settings.system().after(NAVIGATE_SETTINGS_LABEL));
+    }
+}
+
+/// An overlay listing every setting. Changes are applied, and saved, as soon as they are made,
+/// so their effect can be seen right away.
+#[derive(Default)]
+pub struct SettingsScreen {
+    open: bool,
+    selected: usize,
+    rebinding: bool,
+}
+
+impl SettingsScreen {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        self.rebinding = false;
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Row {
+    Language,
+    Difficulty,
+    Mode,
+    Players,
+    GracePeriod,
+    WindowMode,
+    VSync,
+    Background,
+    Letterbox,
+    Theme,
+    MoveLeft,
+    MoveRight,
+    Pause,
+    Start,
+    SwitchLanguage,
+}
+
+static ROWS: [Row; 15] = [
+    Row::Language,
+    Row::Difficulty,
+    Row::Mode,
+    Row::Players,
+    Row::GracePeriod,
+    Row::WindowMode,
+    Row::VSync,
+    Row::Background,
+    Row::Letterbox,
+    Row::Theme,
+    Row::MoveLeft,
+    Row::MoveRight,
+    Row::Pause,
+    Row::Start,
+    Row::SwitchLanguage,
+];
+
+impl Row {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Language => "settings-language",
+            Self::Difficulty => "settings-difficulty",
+            Self::Mode => "settings-mode",
+            Self::Players => "settings-players",
+            Self::GracePeriod => "settings-grace-period",
+            Self::WindowMode => "settings-window-mode",
+            Self::VSync => "settings-vsync",
+            Self::Background => "settings-background",
+            Self::Letterbox => "settings-letterbox",
+            Self::Theme => "settings-theme",
+            Self::MoveLeft => "settings-move-left",
+            Self::MoveRight => "settings-move-right",
+            Self::Pause => "settings-pause",
+            Self::Start => "settings-start",
+            Self::SwitchLanguage => "settings-switch-language",
+        }
+    }
+
+    fn value(self, settings: &Settings, localization: &Localization) -> String {
+        let on_off =
+            |value: bool| localization.get(if value { "settings-on" } else { "settings-off" });
+
+        match self {
+            // Languages are always shown by their own name
+            Self::Language => match settings.language {
+                Language::English => "English".to_string(),
+                Language::Romanian => "Română".to_string(),
+            },
+            Self::Difficulty => localization.get(match settings.difficulty {
+                Difficulty::Easy => "difficulty-easy",
+                Difficulty::Normal => "difficulty-normal",
+                Difficulty::Hard => "difficulty-hard",
+            }),
+            Self::Mode => localization.get(&format!("mode-{}", settings.mode.name())),
+            Self::Players => settings.players.to_string(),
+            Self::GracePeriod => on_off(settings.grace_period),
+            Self::WindowMode => localization.get(match settings.window_mode {
+                WindowMode::Windowed => "window-mode-windowed",
+                WindowMode::Fullscreen => "window-mode-fullscreen",
+            }),
+            Self::VSync => on_off(settings.vsync),
+            Self::Background => on_off(settings.effects.background),
+            Self::Letterbox => on_off(settings.effects.letterbox),
+            Self::Theme => localization.get(&format!("theme-{}", settings.theme)),
+            Self::MoveLeft => settings.controls.left.name().to_string(),
+            Self::MoveRight => settings.controls.right.name().to_string(),
+            Self::Pause => settings.controls.pause.name().to_string(),
+            Self::Start => settings.controls.start.name().to_string(),
+            Self::SwitchLanguage => settings.controls.language.name().to_string(),
+        }
+    }
+
+    /// Moves the setting to its next (`forward`) or previous value. Themes only cycle through
+    /// the `unlocked_themes`.
+    fn adjust(self, settings: &mut Settings, forward: bool, unlocked_themes: &[String]) {
+        match self {
+            Self::Language => settings.language = settings.language.next(),
+            Self::Difficulty => {
+                settings.difficulty = match (settings.difficulty, forward) {
+                    (Difficulty::Easy, true) | (Difficulty::Hard, false) => Difficulty::Normal,
+                    (Difficulty::Normal, true) | (Difficulty::Easy, false) => Difficulty::Hard,
+                    (Difficulty::Hard, true) | (Difficulty::Normal, false) => Difficulty::Easy,
+                }
+            }
+            Self::Mode => {
+                let index = GAME_MODES
+                    .iter()
+                    .position(|mode| *mode == settings.mode)
+                    .unwrap();
+                let step = if forward { 1 } else { GAME_MODES.len() - 1 };
+                settings.mode = GAME_MODES[(index + step) % GAME_MODES.len()];
+            }
+            Self::Players => settings.players = if settings.players == 1 { 2 } else { 1 },
+            Self::GracePeriod => settings.grace_period = !settings.grace_period,
+            Self::WindowMode => {
+                settings.window_mode = match settings.window_mode {
+                    WindowMode::Windowed => WindowMode::Fullscreen,
+                    WindowMode::Fullscreen => WindowMode::Windowed,
+                }
+            }
+            Self::VSync => settings.vsync = !settings.vsync,
+            Self::Background => settings.effects.background = !settings.effects.background,
+            Self::Letterbox => settings.effects.letterbox = !settings.effects.letterbox,
+            Self::Theme if !unlocked_themes.is_empty() => {
+                // A theme which isn't unlocked, or known, starts over from the first one
+                let count = unlocked_themes.len();
+                settings.theme = match unlocked_themes
+                    .iter()
+                    .position(|theme| *theme == settings.theme)
+                {
+                    Some(index) if forward => unlocked_themes[(index + 1) % count].clone(),
+                    Some(index) => unlocked_themes[(index + count - 1) % count].clone(),
+                    None => unlocked_themes[0].clone(),
+                };
+            }
+            Self::Theme
+            | Self::MoveLeft
+            | Self::MoveRight
+            | Self::Pause
+            | Self::Start
+            | Self::SwitchLanguage => (),
+        }
+    }
+
+    fn key_mut(self, settings: &mut Settings) -> Option<&mut Key> {
+        match self {
+            Self::MoveLeft => Some(&mut settings.controls.left),
+            Self::MoveRight => Some(&mut settings.controls.right),
+            Self::Pause => Some(&mut settings.controls.pause),
+            Self::Start => Some(&mut settings.controls.start),
+            Self::SwitchLanguage => Some(&mut settings.controls.language),
+            _ => None,
+        }
+    }
+}
+
+fn navigate_settings(
+    keyboard_input: Res<Input<KeyCode>>,
+    themes: Res<Themes>,
+    high_scores: Res<HighScores>,
+    mut screen: ResMut<SettingsScreen>,
+    mut settings: ResMut<Settings>,
+) {
+    if !screen.open {
+        return;
+    }
+
+    let row = ROWS[screen.selected];
+    let unlocked_themes = themes.unlocked(&high_scores);
+    let mut new_settings = settings.clone();
+
+    if screen.rebinding {
+        // Escape is left alone, it closes the screen, and so are the keys opening the others
+        let reserved = [KeyCode::Escape, KeyCode::F2, KeyCode::F3, KeyCode::F11];
+        let pressed = keyboard_input
+            .get_just_pressed()
+            .filter(|key_code| !reserved.contains(key_code))
+            .find_map(|key_code| Key::from_key_code(*key_code));
+
+        if let Some(key) = pressed {
+            let old_key = std::mem::replace(row.key_mut(&mut new_settings).unwrap(), key);
+            // Controls can't share a key, the one which had it gets the old key instead
+            for other in ROWS.iter().filter(|other| **other != row) {
+                if let Some(other_key) = other.key_mut(&mut new_settings) {
+                    if *other_key == key {
+                        *other_key = old_key;
+                    }
+                }
+            }
+            screen.rebinding = false;
+        }
+    } else if keyboard_input.just_pressed(KeyCode::Up) {
+        screen.selected = (screen.selected + ROWS.len() - 1) % ROWS.len();
+    } else if keyboard_input.just_pressed(KeyCode::Down) {
+        screen.selected = (screen.selected + 1) % ROWS.len();
+    } else if keyboard_input.just_pressed(KeyCode::Left) {
+        row.adjust(&mut new_settings, false, &unlocked_themes);
+    } else if keyboard_input.just_pressed(KeyCode::Right) {
+        row.adjust(&mut new_settings, true, &unlocked_themes);
+    } else if keyboard_input.just_pressed(KeyCode::Return) {
+        if row.key_mut(&mut new_settings).is_some() {
+            screen.rebinding = true;
+        } else {
+            row.adjust(&mut new_settings, true, &unlocked_themes);
+        }
+    }
+
+    // Only touch the settings when something changed, they get saved every time they do
+    if new_settings != *settings {
+        *settings = new_settings;
+    }
+}
+
+struct SettingsPanel;
+struct SettingsText;
+
+fn render_settings(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    screen: Res<SettingsScreen>,
+    settings: Res<Settings>,
+    localization: Res<Localization>,
+    viewport: Res<ArenaViewport>,
+    fonts: Res<Fonts>,
+    panels: Query<Entity, With<SettingsPanel>>,
+    mut texts: Query<&mut Text, With<SettingsText>>,
+) {
+    if !screen.open {
+        for panel in panels.iter() {
+            commands.entity(panel).despawn_recursive();
+        }
+        return;
+    }
+
+    let needs_redraw = screen.is_changed()
+        || settings.is_changed()
+        || localization.is_changed()
+        || viewport.is_changed();
+    if !needs_redraw {
+        return;
+    }
+
+    let font_size = 22.0 * viewport.pixels_per_unit;
+    let section = |value: String, color: Color| TextSection {
+        value,
+        style: TextStyle {
+            font: fonts.ui.clone(),
+            font_size,
+            color,
+        },
+    };
+
+    let mut sections = vec![section(
+        localization.get("settings-title") + "\n\n",
+        Color::rgb(0.0, 0.0, 0.0),
+    )];
+    for (index, row) in ROWS.iter().enumerate() {
+        let selected = index == screen.selected;
+        let value = if selected && screen.rebinding {
+            localization.get("settings-press-key")
+        } else {
+            row.value(&settings, &localization)
+        };
+        let color = if selected {
+            Color::rgb_u8(46, 110, 180)
+        } else {
+            Color::rgb(0.0, 0.0, 0.0)
+        };
+        sections.push(section(
+            format!("{}: {}\n", localization.get(row.label()), value),
+            color,
+        ));
+    }
+    sections.push(section(
+        format!("\n{}", localization.get("settings-hint")),
+        Color::rgb(0.3, 0.3, 0.3),
+    ));
+
+    if let Some(mut text) = texts.iter_mut().next() {
+        text.sections = sections;
+        return;
+    }
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                padding: Rect::all(Val::Px(10.0)),
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(1.0, 1.0, 1.0, 0.92).into()),
+            ..Default::default()
+        })
+        .insert(HudAnchor::top(20.0, 40.0).with_size(Vec2::new(500.0, 520.0)))
+        .insert(SettingsPanel)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text {
+                        sections,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .insert(SettingsText);
+        });
+}
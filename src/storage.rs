@@ -0,0 +1,64 @@
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{fs, path::PathBuf};
+
+/// `name` in the game's folder of the user's config directory.
+pub fn config_file(name: &str) -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join("starhigh").join(name))
+}
+
+/// `name` in the game's folder of the user's data directory.
+pub fn data_file(name: &str) -> Option<PathBuf> {
+    Some(dirs::data_dir()?.join("starhigh").join(name))
+}
+
+/// Reads a value saved by [`save_toml`], falling back to the default when the file is missing
+/// or invalid. `what` names the value in the warning logged for an invalid file.
+pub fn load_toml<T: DeserializeOwned + Default>(path: Option<PathBuf>, what: &str) -> T {
+    let path = match path {
+        Some(path) => path,
+        None => return T::default(),
+    };
+
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(_) => return T::default(),
+    };
+
+    match toml::from_str(&contents) {
+        Ok(value) => value,
+        Err(error) => {
+            warn!("Ignoring invalid {} in {}: {}", what, path.display(), error);
+            T::default()
+        }
+    }
+}
+
+/// Writes `value` to `path`, creating its folder if needed. Failures are only logged.
+pub fn save_toml<T: Serialize>(path: Option<PathBuf>, what: &str, value: &T) {
+    let path = match path {
+        Some(path) => path,
+        None => return,
+    };
+
+    let contents = match toml::to_string_pretty(value) {
+        Ok(contents) => contents,
+        Err(error) => {
+            warn!("Could not serialize the {}: {}", what, error);
+            return;
+        }
+    };
+
+    let written = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&path, contents));
+    if let Err(error) = written {
+        warn!(
+            "Could not save the {} to {}: {}",
+            what,
+            path.display(),
+            error
+        );
+    }
+}
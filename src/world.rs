@@ -1,40 +1,122 @@
+mod achievements;
+mod arena;
+mod campaign;
+mod countdown;
+mod daily;
 mod falling_object;
+mod game_over;
+mod mode;
+mod parallax;
+mod pause;
 mod player;
+mod replay;
 mod scoreboard;
+mod scripted_event;
+mod scripting;
+mod stats;
+mod theme;
+mod versus;
 
+use achievements::*;
+use arena::*;
+use campaign::*;
+use countdown::*;
+use daily::*;
 use falling_object::*;
+use game_over::*;
+use mode::*;
+use parallax::*;
+use pause::*;
 use player::*;
+use replay::*;
 use scoreboard::*;
+use scripted_event::*;
+use scripting::*;
+use stats::*;
+use theme::*;
+use versus::*;
 
+pub use arena::{ArenaViewport, HudAnchor};
+pub use falling_object::Seed;
+pub use player::Player;
+pub use replay::Replay;
+pub use scoreboard::{HighScores, Scoreboard};
+pub use theme::Themes;
+pub use versus::Versus;
+
+use crate::{
+    fonts::Fonts,
+    localization::Localized,
+    settings::{Background, Settings, SettingsScreen},
+};
 use bevy::prelude::*;
 
 pub struct World;
 
 static PLAYER_DEATH_LABEL: &str = "player_death_system";
+static GLOBAL_KEYINPUT_LABEL: &str = "global_keyinput_system";
+static NEW_GAME_LABEL: &str = "new_game_system";
 
 impl Plugin for World {
     fn build(&self, app: &mut AppBuilder) {
         app.add_startup_system(setup.system());
-        app.add_startup_system(build_arena.system());
         app.add_startup_system(
             (|mut commands: Commands,
               mut materials: ResMut<Assets<ColorMaterial>>,
               mut asset_server: Res<AssetServer>| {
                 commands
                     .spawn_bundle(pause_button(&mut materials, &mut asset_server))
-                    .insert(Button::Pause);
+                    .insert(Button::Pause)
+                    .insert(button_anchor());
             })
             .system(),
         );
 
         app.insert_resource(GameState::NotRunning);
-        app.insert_resource(GameStoppedByHandler::No);
-        app.add_system(global_keyinput_handler.system().before(PLAYER_DEATH_LABEL));
+        // The first game gets set up like any that follows a game over
+        app.insert_resource(GameStoppedByHandler::Yes);
+        app.add_event::<GameStarted>();
+        app.add_event::<GameOver>();
+        app.add_event::<StartRequested>();
+        app.add_event::<PauseRequested>();
+        app.add_event::<FocusLost>();
+        app.add_event::<ObjectCaught>();
+        app.add_event::<ObjectMissed>();
+        app.add_event::<PlayerDied>();
+        app.add_event::<EffectRequested>();
+        app.add_system(
+            global_keyinput_handler
+                .system()
+                .label(GLOBAL_KEYINPUT_LABEL)
+                .before(PLAYER_DEATH_LABEL),
+        );
+        app.add_system(
+            start_new_game
+                .system()
+                .label(NEW_GAME_LABEL)
+                .after(GLOBAL_KEYINPUT_LABEL)
+                .before(PLAYER_DEATH_LABEL),
+        );
         app.add_system(handle_player_death.system().label(PLAYER_DEATH_LABEL));
 
+        app.add_plugin(ModePlugin);
+        app.add_plugin(ArenaPlugin);
         app.add_plugin(StarPlugin);
         app.add_plugin(ScoreboardPlugin);
+        app.add_plugin(ThemePlugin);
+        app.add_plugin(ParallaxPlugin);
         app.add_plugin(PlayerPlugin);
+        app.add_plugin(ReplayPlugin);
+        app.add_plugin(DailyPlugin);
+        app.add_plugin(CampaignPlugin);
+        app.add_plugin(ScriptedEventPlugin);
+        app.add_plugin(AchievementsPlugin);
+        app.add_plugin(StatsPlugin);
+        app.add_plugin(GameOverPlugin);
+        app.add_plugin(PausePlugin);
+        app.add_plugin(CountdownPlugin);
+        app.add_plugin(VersusPlugin);
+        app.add_plugin(ScriptingPlugin);
     }
 }
 
@@ -43,40 +125,182 @@ fn global_keyinput_handler(
     mut materials: ResMut<Assets<ColorMaterial>>,
     mut asset_server: Res<AssetServer>,
     mut game_state: ResMut<GameState>,
-    mut lives: ResMut<Lives>,
-    mut scoreboard: ResMut<Scoreboard>,
+    mut countdown: ResMut<Countdown>,
     mut game_stopped: ResMut<GameStoppedByHandler>,
+    mut game_started: EventWriter<GameStarted>,
+    mut start_requests: EventReader<StartRequested>,
+    mut pause_requests: EventReader<PauseRequested>,
+    mut settings_screen: ResMut<SettingsScreen>,
+    settings: Res<Settings>,
     query: Query<(Entity, &Button)>,
     help_box_query: Query<Entity, With<Help>>,
     keyboard_input: Res<Input<KeyCode>>,
 ) {
+    if keyboard_input.just_pressed(KeyCode::Escape) && game_state.is_stopped() {
+        settings_screen.toggle();
+    }
+    if settings_screen.is_open() {
+        return;
+    }
+
+    let controls = settings.controls;
+    let start = keyboard_input.pressed(controls.start.0) || start_requests.iter().next().is_some();
+    let pause = keyboard_input.pressed(controls.pause.0) || pause_requests.iter().next().is_some();
     for (entity, button_kind) in query.iter() {
-        if keyboard_input.pressed(KeyCode::P) && *button_kind == Button::Play {
+        if pause && *button_kind == Button::Play {
             commands.entity(entity).despawn();
             commands
                 .spawn_bundle(pause_button(&mut materials, &mut asset_server))
-                .insert(Button::Pause);
-            game_state.flip();
-        } else if keyboard_input.pressed(KeyCode::S) && *button_kind == Button::Pause {
+                .insert(Button::Pause)
+                .insert(button_anchor());
+            *game_state = GameState::NotRunning;
+        } else if start && *button_kind == Button::Pause {
             commands.entity(entity).despawn();
             commands
                 .spawn_bundle(play_button(&mut materials, &mut asset_server))
-                .insert(Button::Play);
-            game_state.flip();
+                .insert(Button::Play)
+                .insert(button_anchor());
+            *game_state = GameState::CountingDown;
+            countdown.restart();
 
             for help_box in help_box_query.iter() {
                 commands.entity(help_box).despawn();
             }
 
             if *game_stopped == GameStoppedByHandler::Yes {
-                *lives = Lives::new();
-                *scoreboard = Scoreboard::new();
                 *game_stopped = GameStoppedByHandler::No;
+                game_started.send(GameStarted);
             }
         }
     }
 }
 
+/// Asks for a game to start, or to be resumed, as if the start key had been pressed.
+pub struct StartRequested;
+
+/// Asks for the game to be paused, as if the pause key had been pressed.
+pub struct PauseRequested;
+
+/// Sent when the window loses focus, or when the replay being played back says it did.
+pub struct FocusLost;
+
+/// Sent when a game starts from scratch, rather than being resumed.
+pub struct GameStarted;
+
+/// Sent once a game has been lost, or has run out of time.
+pub struct GameOver {
+    /// The best score of any player
+    pub score: u64,
+    /// Whether that score is a new high score for the mode
+    pub new_best: bool,
+}
+
+/// Sent whenever a player catches a falling object.
+pub struct ObjectCaught {
+    pub player: usize,
+    pub kind: ObjectKind,
+}
+
+/// Sent whenever a falling object reaches the ground, for the player it counted against.
+pub struct ObjectMissed {
+    pub player: usize,
+    pub kind: ObjectKind,
+}
+
+/// Sent once for each player who loses their last life.
+pub struct PlayerDied {
+    pub player: usize,
+}
+
+/// Asks for a scripted event to start right away, like when an object which sets one off is
+/// caught or missed.
+pub struct EffectRequested(pub ScriptedEvent);
+
+fn start_new_game(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+    mut game_started: EventReader<GameStarted>,
+    mut speed: ResMut<Speed>,
+    mut spawn_rng: ResMut<SpawnRng>,
+    mut current_mode: ResMut<CurrentMode>,
+    settings: Res<Settings>,
+    arena: Res<Arena>,
+    theme: Res<Theme>,
+    mut players: Query<(
+        Entity,
+        &Player,
+        &mut Scoreboard,
+        &mut Lives,
+        &mut Invulnerable,
+        &mut Misses,
+        &mut Combo,
+        &mut Transform,
+        &mut Visible,
+    )>,
+) {
+    if game_started.iter().next().is_none() {
+        return;
+    }
+
+    // Players are reset in place, rather than respawned, so that the ones left over from the
+    // last game are never mistaken for having lost this one
+    let count = player_count(&settings);
+    let mut spawned = [false; MAX_PLAYERS];
+    for (
+        entity,
+        player,
+        mut scoreboard,
+        mut lives,
+        mut invulnerable,
+        mut misses,
+        mut combo,
+        mut transform,
+        mut visible,
+    ) in players.iter_mut()
+    {
+        if player.0 >= count {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        let spawn = arena.player_spawn(player.0, count);
+        transform.translation.x = spawn.x;
+        transform.translation.y = spawn.y;
+        *scoreboard = Scoreboard::new();
+        *lives = Lives::new(settings.max_lives);
+        misses.0 = 0;
+        *combo = Combo::default();
+        *invulnerable = Invulnerable::default();
+        visible.is_visible = true;
+        spawned[player.0] = true;
+    }
+    for number in (0..count).filter(|number| !spawned[*number]) {
+        spawn_player(
+            &mut commands,
+            &mut materials,
+            asset_server.load(theme.cloud.as_str()),
+            &arena,
+            Player(number),
+            count,
+            settings.max_lives,
+        );
+    }
+
+    *speed = Speed::new(settings.mode.difficulty().unwrap_or(settings.difficulty));
+    spawn_rng.restart();
+    *current_mode = CurrentMode::new(settings.mode);
+}
+
+/// Whether one of the screens opened between games is in front of everything else.
+fn any_screen_open(
+    settings_screen: &SettingsScreen,
+    achievements_screen: &AchievementsScreen,
+    stats_screen: &StatsScreen,
+) -> bool {
+    settings_screen.is_open() || achievements_screen.is_open() || stats_screen.is_open()
+}
+
 #[derive(PartialEq, Eq, Clone, Copy)]
 enum Button {
     Pause,
@@ -91,11 +315,6 @@ fn pause_button(
         material: materials.add(asset_server.load("pause.png").into()),
         style: Style {
             position_type: PositionType::Absolute,
-            position: Rect {
-                bottom: Val::Px(5.0),
-                left: Val::Px(5.0),
-                ..Default::default()
-            },
             ..Default::default()
         },
         calculated_size: CalculatedSize {
@@ -113,11 +332,6 @@ fn play_button(
         material: materials.add(asset_server.load("play.png").into()),
         style: Style {
             position_type: PositionType::Absolute,
-            position: Rect {
-                bottom: Val::Px(5.0),
-                left: Val::Px(5.0),
-                ..Default::default()
-            },
             ..Default::default()
         },
         calculated_size: CalculatedSize {
@@ -127,72 +341,55 @@ fn play_button(
     }
 }
 
-struct Wall;
+fn button_anchor() -> HudAnchor {
+    HudAnchor::bottom(5.0, 5.0).with_size(Vec2::new(40.0, 40.0))
+}
+
 struct Help;
 
 fn setup(
     mut commands: Commands,
     mut materials: ResMut<Assets<ColorMaterial>>,
     asset_server: Res<AssetServer>,
+    theme: Res<Theme>,
+    fonts: Res<Fonts>,
 ) {
-    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
+    commands
+        .spawn_bundle(OrthographicCameraBundle::new_2d())
+        .insert(ArenaCamera);
     commands.spawn_bundle(UiCameraBundle::default());
-    commands.spawn_bundle(SpriteBundle {
-        material: materials.add(asset_server.load("backgroun2d.png").into()),
-        transform: Transform::from_xyz(0.0, 0.0, 0.0),
-        sprite: Sprite::new(Vec2::new(600.0, 600.0)),
-        ..Default::default()
-    });
-
     commands
         .spawn_bundle(SpriteBundle {
-            material: materials.add(asset_server.load("help.png").into()),
-            transform: Transform::from_xyz(40.0, 0.0, 0.0),
-            sprite: Sprite::new(Vec2::new(238.0, 150.0)),
+            material: materials.add(ColorMaterial::modulated_texture(
+                asset_server.load(theme.background.as_str()),
+                theme.background_tint(),
+            )),
+            transform: Transform::from_xyz(0.0, 0.0, BACKGROUND_DEPTH),
+            sprite: Sprite::new(Vec2::new(600.0, 600.0)),
             ..Default::default()
         })
-        .insert(Help);
-}
+        .insert(Background)
+        .insert(Themed::Background);
 
-fn build_arena(mut commands: Commands, mut materials: ResMut<Assets<ColorMaterial>>) {
-    enum Axis {
-        Vertical,
-        Horizontal,
-    }
-
-    fn wall_at(
-        x: f32,
-        y: f32,
-        axis: Axis,
-        materials: &mut ResMut<Assets<ColorMaterial>>,
-    ) -> SpriteBundle {
-        SpriteBundle {
-            material: materials.add(ColorMaterial::color(Color::rgb_u8(146, 208, 209))),
-            transform: Transform::from_xyz(x, y, 0.0),
-            sprite: match axis {
-                Axis::Vertical => Sprite::new(Vec2::new(20.0, 1199.0)),
-                Axis::Horizontal => Sprite::new(Vec2::new(700.0, 20.0)),
-            },
-            ..Default::default()
-        }
-    }
-
-    // right wall
-    commands
-        .spawn_bundle(wall_at(260.0, -270.0, Axis::Vertical, &mut materials))
-        .insert(Wall);
-    // bottom wall
-    commands
-        .spawn_bundle(wall_at(220.0, -290.0, Axis::Horizontal, &mut materials))
-        .insert(Wall);
-    // left wall
     commands
-        .spawn_bundle(wall_at(-140.0, -275.0, Axis::Vertical, &mut materials))
-        .insert(Wall);
-    // top wall
-    commands
-        .spawn_bundle(wall_at(200.0, 290.0, Axis::Horizontal, &mut materials))
-        .insert(Wall);
+        .spawn_bundle(Text2dBundle {
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: fonts.ui.clone(),
+                    font_size: 24.0,
+                    color: Color::rgb(0.0, 0.0, 0.0),
+                },
+                TextAlignment {
+                    vertical: VerticalAlign::Center,
+                    horizontal: HorizontalAlign::Center,
+                },
+            ),
+            transform: Transform::from_xyz(60.0, 0.0, 1.0),
+            ..Default::default()
+        })
+        .insert(Localized("help"))
+        .insert(Help);
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -200,18 +397,20 @@ fn build_arena(mut commands: Commands, mut materials: ResMut<Assets<ColorMateria
 enum GameState {
     Running,
     NotRunning,
+    /// Counting down to the game starting, or resuming. The clouds can already move, but
+    /// nothing falls yet.
+    CountingDown,
 }
 
 impl GameState {
+    /// Whether the game is frozen, counting down included.
     pub fn is_not_running(self) -> bool {
-        Self::NotRunning == self
+        Self::Running != self
     }
 
-    fn flip(&mut self) {
-        *self = match *self {
-            Self::Running => Self::NotRunning,
-            Self::NotRunning => Self::Running,
-        };
+    /// Whether the game is paused, over, or has yet to start, with no countdown going.
+    pub fn is_stopped(self) -> bool {
+        Self::NotRunning == self
     }
 }
 
@@ -227,41 +426,39 @@ fn handle_player_death(
     mut asset_server: Res<AssetServer>,
     mut game_running: ResMut<GameState>,
     mut game_stopped: ResMut<GameStoppedByHandler>,
-    lives: Res<Lives>,
-    mut high_score: ResMut<HighScore>,
-    scoreboard: Res<Scoreboard>,
-    mut displayed_boards: Query<(&mut Text, &BoardType)>,
-    buttons: Query<(Entity, &Button)>,
+    mut game_over_events: EventWriter<GameOver>,
+    current_mode: Res<CurrentMode>,
+    mut high_scores: ResMut<HighScores>,
+    buttons: Query<Entity, With<Button>>,
     falling_objects: Query<Entity, With<ObjectKind>>,
-    players: Query<Entity, With<Player>>,
+    players: Query<(&Scoreboard, &Lives), With<Player>>,
 ) {
-    if lives.is_dead() && *game_stopped == GameStoppedByHandler::No {
+    // With two players, the game goes on until both are out
+    let everyone_dead = players.iter().all(|(_, lives)| lives.is_dead());
+    let game_over = everyone_dead || current_mode.is_finished();
+    if game_over && *game_stopped == GameStoppedByHandler::No {
         *game_stopped = GameStoppedByHandler::Yes;
         for object in falling_objects.iter() {
             commands.entity(object).despawn();
         }
         *game_running = GameState::NotRunning;
-        for (mut text, kind) in displayed_boards.iter_mut() {
-            if *kind != BoardType::HighScore {
-                continue;
-            }
-            high_score.value = scoreboard.score();
-            text.sections[0].value = high_score.value.to_string();
-            high_score.update_file();
+        let best_score = players.iter().map(|(s, _)| s.score()).max().unwrap_or(0);
+        let recorded =
+            !current_mode.is_practice() && high_scores.record(current_mode.mode, best_score);
+        if recorded {
+            high_scores.update_file();
         }
-        for (button, kind) in buttons.iter() {
-            if *kind == Button::Play {
-                commands.entity(button).despawn();
-            }
+        game_over_events.send(GameOver {
+            score: best_score,
+            new_best: recorded,
+        });
+        // Games can also end while paused, from the pause menu
+        for button in buttons.iter() {
+            commands.entity(button).despawn();
         }
         commands
             .spawn_bundle(pause_button(&mut materials, &mut asset_server))
-            .insert(Button::Pause);
-
-        for player in players.iter() {
-            commands.entity(player).despawn();
-        }
-
-        spawn_player(commands, materials, asset_server);
+            .insert(Button::Pause)
+            .insert(button_anchor());
     }
 }
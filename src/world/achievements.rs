@@ -0,0 +1,262 @@
+mod screen;
+
+pub use screen::AchievementsScreen;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeSet, VecDeque},
+    path::PathBuf,
+};
+
+use super::{
+    arena::HudAnchor,
+    falling_object::{ObjectKind, Speed},
+    mode::CurrentMode,
+    player::{Player, MAX_PLAYERS},
+    replay::GameClock,
+    scoreboard::{Combo, Lives},
+    GameStarted, GameState, ObjectCaught, NEW_GAME_LABEL,
+};
+use crate::{embedded::AssetSource, fonts::Fonts, localization::Localization, storage};
+
+const TOAST_SECONDS: f32 = 3.0;
+
+/// Unlocks the achievements in `assets/achievements.toml` as they are earned, announcing each
+/// with a toast.
+pub(super) struct AchievementsPlugin;
+
+impl Plugin for AchievementsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let achievements = Achievements::load(app.world().get_resource::<AssetSource>().unwrap());
+        app.insert_resource(achievements);
+        app.insert_resource(UnlockedAchievements::load_from_file());
+        app.insert_resource(GameProgress::default());
+        app.insert_resource(Toasts::default());
+
+        app.add_startup_system(spawn_toast.system());
+        app.add_system(track_progress.system().after(NEW_GAME_LABEL));
+        app.add_system(unlock_achievements.system());
+        app.add_system(show_toasts.system());
+        app.add_plugin(screen::AchievementsScreenPlugin);
+    }
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct Achievements {
+    pub achievements: Vec<Achievement>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct Achievement {
+    /// Titles are looked up as `achievement-<name>`, descriptions as
+    /// `achievement-<name>-description`
+    pub name: String,
+    pub goal: Goal,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Goal {
+    Stars { count: u64 },
+    Hearts { count: u64 },
+    Combo { count: u64 },
+    LastLife { seconds: f32 },
+    Speed { value: f32 },
+}
+
+impl Achievements {
+    pub fn load(source: &AssetSource) -> Self {
+        let path = PathBuf::from("achievements.toml");
+
+        let contents = match source.read_to_string(&path) {
+            Some(contents) => contents,
+            None => {
+                warn!("Could not load {}", path.display());
+                return Self::default();
+            }
+        };
+
+        match toml::from_str(&contents) {
+            Ok(achievements) => achievements,
+            Err(error) => {
+                warn!(
+                    "Could not load the achievements in {}: {}",
+                    path.display(),
+                    error
+                );
+                Self::default()
+            }
+        }
+    }
+}
+
+/// The names of the achievements earned so far, stored as `achievements.toml` in the user's
+/// data directory.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UnlockedAchievements {
+    pub unlocked: BTreeSet<String>,
+}
+
+impl UnlockedAchievements {
+    pub fn load_from_file() -> Self {
+        storage::load_toml(storage::data_file("achievements.toml"), "achievements")
+    }
+
+    pub fn update_file(&self) {
+        storage::save_toml(
+            storage::data_file("achievements.toml"),
+            "achievements",
+            self,
+        );
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.unlocked.contains(name)
+    }
+}
+
+/// What each player has done in the current game, as far as achievements are concerned.
+#[derive(Default)]
+struct GameProgress {
+    stars: [u64; MAX_PLAYERS],
+    hearts: [u64; MAX_PLAYERS],
+    /// Seconds spent in a row with a single life left
+    last_life: [f32; MAX_PLAYERS],
+}
+
+fn track_progress(
+    clock: Res<GameClock>,
+    game_state: Res<GameState>,
+    current_mode: Res<CurrentMode>,
+    mut progress: ResMut<GameProgress>,
+    mut game_started: EventReader<GameStarted>,
+    mut object_caught: EventReader<ObjectCaught>,
+    players: Query<(&Player, &Lives)>,
+) {
+    if game_started.iter().next().is_some() {
+        *progress = GameProgress::default();
+    }
+
+    for caught in object_caught.iter() {
+        match caught.kind {
+            ObjectKind::STAR => progress.stars[caught.player] += 1,
+            ObjectKind::HEART => progress.hearts[caught.player] += 1,
+            _ => (),
+        }
+    }
+
+    if game_state.is_not_running() {
+        return;
+    }
+
+    for (player, lives) in players.iter() {
+        if current_mode.has_lives() && lives.count() == 1 {
+            progress.last_life[player.0] += clock.delta_seconds();
+        } else {
+            progress.last_life[player.0] = 0.0;
+        }
+    }
+}
+
+fn unlock_achievements(
+    game_state: Res<GameState>,
+    current_mode: Res<CurrentMode>,
+    speed: Res<Speed>,
+    achievements: Res<Achievements>,
+    progress: Res<GameProgress>,
+    mut unlocked: ResMut<UnlockedAchievements>,
+    mut toasts: ResMut<Toasts>,
+    combos: Query<&Combo, With<Player>>,
+) {
+    if game_state.is_not_running() || current_mode.is_practice() {
+        return;
+    }
+
+    let best_combo = combos.iter().map(|combo| combo.best).max().unwrap_or(0);
+    let reached = |goal: Goal| match goal {
+        Goal::Stars { count } => progress.stars.iter().any(|stars| *stars >= count),
+        Goal::Hearts { count } => progress.hearts.iter().any(|hearts| *hearts >= count),
+        Goal::Combo { count } => best_combo >= count,
+        Goal::LastLife { seconds } => progress.last_life.iter().any(|time| *time >= seconds),
+        // Meteor showers would make this too easy
+        Goal::Speed { value } => speed.base_value() >= value,
+    };
+
+    let mut changed = false;
+    for achievement in achievements.achievements.iter() {
+        if unlocked.contains(&achievement.name) || !reached(achievement.goal) {
+            continue;
+        }
+
+        unlocked.unlocked.insert(achievement.name.clone());
+        toasts.queue.push_back(achievement.name.clone());
+        changed = true;
+    }
+    if changed {
+        unlocked.update_file();
+    }
+}
+
+/// Achievements waiting to be announced, one at a time.
+#[derive(Default)]
+struct Toasts {
+    queue: VecDeque<String>,
+    showing: Option<(String, f32)>,
+}
+
+struct Toast;
+
+fn spawn_toast(mut commands: Commands, fonts: Res<Fonts>) {
+    commands
+        .spawn_bundle(TextBundle {
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: fonts.ui.clone(),
+                    font_size: 22.0,
+                    color: Color::rgb_u8(46, 110, 180),
+                },
+                Default::default(),
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(Toast)
+        .insert(HudAnchor::bottom(150.0, 5.0).with_font_size(22.0));
+}
+
+fn show_toasts(
+    time: Res<Time>,
+    localization: Res<Localization>,
+    mut toasts: ResMut<Toasts>,
+    mut labels: Query<&mut Text, With<Toast>>,
+) {
+    if let Some((_, time_left)) = toasts.showing.as_mut() {
+        *time_left -= time.delta_seconds();
+        if *time_left <= 0.0 {
+            toasts.showing = None;
+        }
+    }
+    if toasts.showing.is_none() {
+        toasts.showing = toasts.queue.pop_front().map(|name| (name, TOAST_SECONDS));
+    }
+
+    let value = match &toasts.showing {
+        Some((name, _)) => localization.format(
+            "achievement-unlocked",
+            &[("title", localization.get(&format!("achievement-{}", name)))],
+        ),
+        None => String::new(),
+    };
+    for mut text in labels.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use bevy::prelude::*;
+
+use super::{Achievements, UnlockedAchievements};
+use crate::{
+    fonts::Fonts,
+    localization::Localization,
+    settings::SettingsScreen,
+    world::{ArenaViewport, GameState, HudAnchor},
+};
+
+pub(super) struct AchievementsScreenPlugin;
+
+impl Plugin for AchievementsScreenPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(AchievementsScreen::default());
+        app.add_system(toggle_achievements.system());
+        app.add_system(render_achievements.system());
+    }
+}
+
+/// An overlay listing every achievement, earned or not. Opened with F2 between games.
+#[derive(Default)]
+pub struct AchievementsScreen {
+    open: bool,
+}
+
+impl AchievementsScreen {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+}
+
+fn toggle_achievements(
+    keyboard_input: Res<Input<KeyCode>>,
+    game_state: Res<GameState>,
+    settings_screen: Res<SettingsScreen>,
+    mut screen: ResMut<AchievementsScreen>,
+) {
+    // Starting a game, or opening the settings, gets it out of the way
+    if screen.open && (!game_state.is_stopped() || settings_screen.is_open()) {
+        screen.open = false;
+    } else if keyboard_input.just_pressed(KeyCode::F2)
+        && game_state.is_stopped()
+        && !settings_screen.is_open()
+    {
+        screen.open = !screen.open;
+    }
+}
+
+struct AchievementsPanel;
+struct AchievementsText;
+
+fn render_achievements(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    screen: Res<AchievementsScreen>,
+    achievements: Res<Achievements>,
+    unlocked: Res<UnlockedAchievements>,
+    localization: Res<Localization>,
+    viewport: Res<ArenaViewport>,
+    fonts: Res<Fonts>,
+    panels: Query<Entity, With<AchievementsPanel>>,
+    mut texts: Query<&mut Text, With<AchievementsText>>,
+) {
+    if !screen.open {
+        for panel in panels.iter() {
+            commands.entity(panel).despawn_recursive();
+        }
+        return;
+    }
+
+    let needs_redraw = screen.is_changed()
+        || unlocked.is_changed()
+        || localization.is_changed()
+        || viewport.is_changed();
+    if !needs_redraw {
+        return;
+    }
+
+    let font_size = 20.0 * viewport.pixels_per_unit;
+    let section = |value: String, color: Color| TextSection {
+        value,
+        style: TextStyle {
+            font: fonts.ui.clone(),
+            font_size,
+            color,
+        },
+    };
+
+    let earned = achievements
+        .achievements
+        .iter()
+        .filter(|achievement| unlocked.contains(&achievement.name))
+        .count();
+    let mut sections = vec![section(
+        localization.format(
+            "achievements-title",
+            &[
+                ("earned", earned.to_string()),
+                ("total", achievements.achievements.len().to_string()),
+            ],
+        ) + "\n\n",
+        Color::rgb(0.0, 0.0, 0.0),
+    )];
+    for achievement in achievements.achievements.iter() {
+        // Locked achievements are greyed out, but still say how to earn them
+        let color = if unlocked.contains(&achievement.name) {
+            Color::rgb_u8(46, 110, 180)
+        } else {
+            Color::rgb(0.55, 0.55, 0.55)
+        };
+        sections.push(section(
+            format!(
+                "{}\n  {}\n",
+                localization.get(&format!("achievement-{}", achievement.name)),
+                localization.get(&format!("achievement-{}-description", achievement.name)),
+            ),
+            color,
+        ));
+    }
+    sections.push(section(
+        format!("\n{}", localization.get("achievements-hint")),
+        Color::rgb(0.3, 0.3, 0.3),
+    ));
+
+    if let Some(mut text) = texts.iter_mut().next() {
+        text.sections = sections;
+        return;
+    }
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                padding: Rect::all(Val::Px(10.0)),
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(1.0, 1.0, 1.0, 0.92).into()),
+            ..Default::default()
+        })
+        .insert(HudAnchor::top(20.0, 40.0).with_size(Vec2::new(500.0, 520.0)))
+        .insert(AchievementsPanel)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text {
+                        sections,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .insert(AchievementsText);
+        });
+}
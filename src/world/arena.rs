@@ -0,0 +1,419 @@
+mod obstacle;
+
+pub use obstacle::*;
+
+use std::{ops::Range, path::PathBuf};
+
+use bevy::{
+    prelude::*,
+    render::camera::{Camera, CameraProjection, OrthographicProjection},
+};
+use serde::Deserialize;
+
+use super::theme::Theme;
+use crate::{
+    embedded::AssetSource,
+    settings::{Letterbox, Settings, WindowMode},
+};
+
+static SCALE_CAMERA_LABEL: &str = "scale_camera_system";
+
+pub(super) struct ArenaPlugin;
+
+impl Plugin for ArenaPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        // Whoever builds the app may have already picked a layout
+        if app.world().get_resource::<Arena>().is_none() {
+            let world = app.world();
+            let layout = world.get_resource::<Settings>().unwrap().arena.clone();
+            let arena = Arena::load(world.get_resource::<AssetSource>().unwrap(), &layout);
+            app.insert_resource(arena);
+        }
+        app.insert_resource(ArenaViewport::default());
+        app.insert_resource(ClearColor(Color::BLACK));
+        app.add_startup_system(build_arena.system());
+        app.add_startup_system(spawn_letterbox.system());
+        app.add_system(rebuild_arena.system());
+        app.add_system(scale_camera.system().label(SCALE_CAMERA_LABEL));
+        app.add_system(layout_hud.system().after(SCALE_CAMERA_LABEL));
+        app.add_system(toggle_fullscreen.system());
+
+        app.add_plugin(ObstaclePlugin);
+    }
+}
+
+/// The logical playfield. Everything in the world is positioned in these units, and the
+/// camera is scaled so that all of it fits in the window, with black bars filling the rest.
+///
+/// Layouts are read from `assets/arenas/<name>.toml`, any value missing from the file is taken
+/// from the classic layout.
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct Arena {
+    pub width: f32,
+    pub height: f32,
+    pub left_wall: f32,
+    pub right_wall: f32,
+    pub bottom_wall: f32,
+    pub top_wall: f32,
+    pub spawn_y: f32,
+    pub obstacles: Vec<ObstacleLayout>,
+    /// The name the layout was loaded by
+    #[serde(skip)]
+    pub name: String,
+}
+
+impl Default for Arena {
+    fn default() -> Self {
+        Self {
+            width: 540.0,
+            height: 600.0,
+            left_wall: -140.0,
+            right_wall: 260.0,
+            bottom_wall: -290.0,
+            top_wall: 290.0,
+            spawn_y: 260.0,
+            obstacles: Vec::new(),
+            name: "classic".to_string(),
+        }
+    }
+}
+
+impl Arena {
+    pub const WALL_THICKNESS: f32 = 20.0;
+
+    /// Loads the layout called `name`, falling back to the classic layout if it can't be read.
+    pub fn load(source: &AssetSource, name: &str) -> Self {
+        let path = PathBuf::from("arenas").join(name).with_extension("toml");
+
+        let arena = match Self::parse(source.read_to_string(&path)) {
+            Ok(arena) => arena,
+            Err(error) => {
+                warn!("Could not load arena layout {}: {}", path.display(), error);
+                Self::default()
+            }
+        };
+        Self {
+            name: name.to_string(),
+            ..arena
+        }
+    }
+
+    fn parse(contents: Option<String>) -> Result<Self, String> {
+        let contents = contents.ok_or_else(|| "the file does not exist".to_string())?;
+        let arena: Self = toml::from_str(&contents).map_err(|e| e.to_string())?;
+
+        if arena.spawn_x_range().is_empty() {
+            return Err("the walls are too close to each other".to_string());
+        }
+
+        Ok(arena)
+    }
+
+    /// The horizontal range falling objects may be spawned in, keeping them clear of the walls.
+    pub fn spawn_x_range(&self) -> Range<f32> {
+        (self.left_wall + Self::WALL_THICKNESS)..(self.right_wall - 4.0 * Self::WALL_THICKNESS)
+    }
+
+    /// Where the cloud of player `number` (counted from 0) starts, out of `count` players.
+    pub fn player_spawn(&self, number: usize, count: usize) -> Vec2 {
+        let center = (self.left_wall + self.right_wall) / 2.0;
+        let offset = (number as f32 - (count as f32 - 1.0) / 2.0) * 130.0;
+        Vec2::new(center + offset, self.bottom_wall + 90.0)
+    }
+}
+
+/// Where the arena ended up on screen, in window pixels. Updated whenever the window changes
+/// size, so that the UI can follow the scaled playfield.
+#[derive(Clone, Copy)]
+pub struct ArenaViewport {
+    pub pixels_per_unit: f32,
+    pub offset: Vec2,
+}
+
+impl Default for ArenaViewport {
+    fn default() -> Self {
+        Self {
+            pixels_per_unit: 1.0,
+            offset: Vec2::ZERO,
+        }
+    }
+}
+
+pub struct ArenaCamera;
+
+pub struct Wall;
+
+fn build_arena(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    arena: Res<Arena>,
+    theme: Res<Theme>,
+) {
+    spawn_walls(&mut commands, &mut materials, &arena, theme.wall_color());
+}
+
+fn spawn_walls(
+    commands: &mut Commands,
+    materials: &mut Assets<ColorMaterial>,
+    arena: &Arena,
+    color: Color,
+) {
+    enum Axis {
+        Vertical,
+        Horizontal,
+    }
+
+    let horizontal_length = arena.right_wall - arena.left_wall + Arena::WALL_THICKNESS;
+    let vertical_length = arena.top_wall - arena.bottom_wall + Arena::WALL_THICKNESS;
+
+    let mut wall_at = |x: f32, y: f32, axis: Axis| SpriteBundle {
+        material: materials.add(ColorMaterial::color(color)),
+        transform: Transform::from_xyz(x, y, 0.0),
+        sprite: match axis {
+            Axis::Vertical => Sprite::new(Vec2::new(Arena::WALL_THICKNESS, vertical_length)),
+            Axis::Horizontal => Sprite::new(Vec2::new(horizontal_length, Arena::WALL_THICKNESS)),
+        },
+        ..Default::default()
+    };
+
+    let center_x = (arena.left_wall + arena.right_wall) / 2.0;
+    let center_y = (arena.bottom_wall + arena.top_wall) / 2.0;
+
+    // right wall
+    commands
+        .spawn_bundle(wall_at(arena.right_wall, center_y, Axis::Vertical))
+        .insert(Wall);
+    // bottom wall
+    commands
+        .spawn_bundle(wall_at(center_x, arena.bottom_wall, Axis::Horizontal))
+        .insert(Wall);
+    // left wall
+    commands
+        .spawn_bundle(wall_at(arena.left_wall, center_y, Axis::Vertical))
+        .insert(Wall);
+    // top wall
+    commands
+        .spawn_bundle(wall_at(center_x, arena.top_wall, Axis::Horizontal))
+        .insert(Wall);
+}
+
+/// Covers everything outside of the arena, so that nothing drawn past its edges (like the
+/// background) shows up when the window's aspect ratio doesn't match the arena's.
+fn spawn_letterbox(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    arena: Res<Arena>,
+    settings: Res<Settings>,
+) {
+    spawn_letterbox_bars(
+        &mut commands,
+        &mut materials,
+        &arena,
+        settings.effects.letterbox,
+    );
+}
+
+fn spawn_letterbox_bars(
+    commands: &mut Commands,
+    materials: &mut Assets<ColorMaterial>,
+    arena: &Arena,
+    visible: bool,
+) {
+    // Large enough to cover the window even when it is very far from the arena's aspect ratio
+    let extent = 10.0 * arena.width.max(arena.height);
+    let material = materials.add(ColorMaterial::color(Color::BLACK));
+
+    let bars = [
+        (
+            Vec2::new(-(arena.width + extent) / 2.0, 0.0),
+            Vec2::new(extent, extent),
+        ),
+        (
+            Vec2::new((arena.width + extent) / 2.0, 0.0),
+            Vec2::new(extent, extent),
+        ),
+        (
+            Vec2::new(0.0, -(arena.height + extent) / 2.0),
+            Vec2::new(arena.width, extent),
+        ),
+        (
+            Vec2::new(0.0, (arena.height + extent) / 2.0),
+            Vec2::new(arena.width, extent),
+        ),
+    ];
+
+    for (position, size) in bars.iter() {
+        commands
+            .spawn_bundle(SpriteBundle {
+                material: material.clone(),
+                transform: Transform::from_xyz(position.x, position.y, 50.0),
+                sprite: Sprite::new(*size),
+                visible: Visible {
+                    is_visible: visible,
+                    is_transparent: true,
+                },
+                ..Default::default()
+            })
+            .insert(Letterbox);
+    }
+}
+
+/// Replaces everything built from the layout when another one is swapped in, like the ones
+/// campaign levels are played in.
+fn rebuild_arena(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    arena: Res<Arena>,
+    theme: Res<Theme>,
+    settings: Res<Settings>,
+    built: Query<Entity, Or<(With<Wall>, With<Obstacle>, With<Letterbox>)>>,
+) {
+    if !arena.is_changed() || arena.is_added() {
+        return;
+    }
+
+    for entity in built.iter() {
+        commands.entity(entity).despawn();
+    }
+    spawn_walls(&mut commands, &mut materials, &arena, theme.wall_color());
+    spawn_obstacle_sprites(&mut commands, &mut materials, &arena);
+    spawn_letterbox_bars(
+        &mut commands,
+        &mut materials,
+        &arena,
+        settings.effects.letterbox,
+    );
+}
+
+fn scale_camera(
+    arena: Res<Arena>,
+    windows: Res<Windows>,
+    mut viewport: ResMut<ArenaViewport>,
+    mut cameras: Query<(&mut Camera, &mut OrthographicProjection), With<ArenaCamera>>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+
+    let pixels_per_unit = (window.width() / arena.width).min(window.height() / arena.height);
+    if pixels_per_unit <= 0.0 {
+        // The window is minimized
+        return;
+    }
+
+    let offset = Vec2::new(
+        (window.width() - arena.width * pixels_per_unit) / 2.0,
+        (window.height() - arena.height * pixels_per_unit) / 2.0,
+    );
+
+    if (viewport.pixels_per_unit - pixels_per_unit).abs() > f32::EPSILON
+        || viewport.offset != offset
+    {
+        *viewport = ArenaViewport {
+            pixels_per_unit,
+            offset,
+        };
+    }
+
+    for (mut camera, mut projection) in cameras.iter_mut() {
+        let scale = 1.0 / pixels_per_unit;
+        if (projection.scale - scale).abs() > f32::EPSILON {
+            projection.scale = scale;
+            // Bevy only rebuilds the projection matrix on resize, not when the scale changes
+            camera.projection_matrix = projection.get_projection_matrix();
+        }
+    }
+}
+
+pub enum HudEdge {
+    Top(f32),
+    Bottom(f32),
+}
+
+/// Places a UI node relative to the arena instead of the window. All values are in arena
+/// units and get converted to window pixels whenever the viewport changes.
+pub struct HudAnchor {
+    pub left: f32,
+    pub edge: HudEdge,
+    pub size: Option<Vec2>,
+    pub font_size: Option<f32>,
+}
+
+impl HudAnchor {
+    pub fn top(left: f32, top: f32) -> Self {
+        Self {
+            left,
+            edge: HudEdge::Top(top),
+            size: None,
+            font_size: None,
+        }
+    }
+
+    pub fn bottom(left: f32, bottom: f32) -> Self {
+        Self {
+            left,
+            edge: HudEdge::Bottom(bottom),
+            size: None,
+            font_size: None,
+        }
+    }
+
+    pub fn with_size(mut self, size: Vec2) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    pub fn with_font_size(mut self, font_size: f32) -> Self {
+        self.font_size = Some(font_size);
+        self
+    }
+}
+
+fn layout_hud(
+    viewport: Res<ArenaViewport>,
+    mut query: Query<(
+        &HudAnchor,
+        ChangeTrackers<HudAnchor>,
+        &mut Style,
+        Option<&mut Text>,
+    )>,
+) {
+    let scale = viewport.pixels_per_unit;
+    for (anchor, anchor_tracker, mut style, text) in query.iter_mut() {
+        if !viewport.is_changed() && !anchor_tracker.is_changed() {
+            continue;
+        }
+
+        style.position.left = Val::Px(viewport.offset.x + anchor.left * scale);
+        match anchor.edge {
+            HudEdge::Top(top) => style.position.top = Val::Px(viewport.offset.y + top * scale),
+            HudEdge::Bottom(bottom) => {
+                style.position.bottom = Val::Px(viewport.offset.y + bottom * scale)
+            }
+        }
+
+        if let Some(size) = anchor.size {
+            style.size = Size::new(Val::Px(size.x * scale), Val::Px(size.y * scale));
+        }
+
+        if let (Some(font_size), Some(mut text)) = (anchor.font_size, text) {
+            for section in text.sections.iter_mut() {
+                section.style.font_size = font_size * scale;
+            }
+        }
+    }
+}
+
+/// The window itself gets updated, and the choice saved, by the settings plugin.
+fn toggle_fullscreen(keyboard_input: Res<Input<KeyCode>>, mut settings: ResMut<Settings>) {
+    if !keyboard_input.just_pressed(KeyCode::F11) {
+        return;
+    }
+
+    settings.window_mode = match settings.window_mode {
+        WindowMode::Windowed => WindowMode::Fullscreen,
+        WindowMode::Fullscreen => WindowMode::Windowed,
+    };
+}
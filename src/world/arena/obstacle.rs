@@ -0,0 +1,174 @@
+use bevy::{
+    prelude::*,
+    sprite::collide_aabb::{collide, Collision},
+};
+use serde::Deserialize;
+
+use super::{
+    super::{
+        falling_object::{Drift, ObjectKind, FALLING_OBJECT_GRAVITY_LABEL},
+        replay::GameClock,
+        GameState,
+    },
+    Arena,
+};
+
+pub(super) struct ObstaclePlugin;
+
+impl Plugin for ObstaclePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(spawn_obstacles.system());
+        app.add_system(move_obstacles.system().before(FALLING_OBJECT_GRAVITY_LABEL));
+        app.add_system(
+            deflect_falling_objects
+                .system()
+                .after(FALLING_OBJECT_GRAVITY_LABEL),
+        );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ObstacleKind {
+    /// Falling objects slide off of it
+    Platform,
+    /// Falling objects get flung away from it
+    Bumper,
+}
+
+impl ObstacleKind {
+    fn deflection(self) -> f32 {
+        match self {
+            Self::Platform => 2.0,
+            Self::Bumper => 6.0,
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            Self::Platform => Color::rgb_u8(146, 208, 209),
+            Self::Bumper => Color::rgb_u8(242, 153, 74),
+        }
+    }
+}
+
+/// An obstacle as written in an arena layout file.
+#[derive(Clone, Deserialize)]
+pub struct ObstacleLayout {
+    pub kind: ObstacleKind,
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    #[serde(default)]
+    pub motion: Option<ObstacleMotion>,
+}
+
+/// Makes an obstacle swing back and forth around its position.
+#[derive(Clone, Copy, Deserialize)]
+pub struct ObstacleMotion {
+    /// The furthest the obstacle gets from its position
+    pub offset: [f32; 2],
+    /// How many seconds a full swing takes
+    pub period: f32,
+}
+
+pub struct Obstacle {
+    kind: ObstacleKind,
+    origin: Vec2,
+    motion: Option<ObstacleMotion>,
+    elapsed: f32,
+}
+
+fn spawn_obstacles(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    arena: Res<Arena>,
+) {
+    spawn_obstacle_sprites(&mut commands, &mut materials, &arena);
+}
+
+pub(in crate::world) fn spawn_obstacle_sprites(
+    commands: &mut Commands,
+    materials: &mut Assets<ColorMaterial>,
+    arena: &Arena,
+) {
+    for layout in arena.obstacles.iter() {
+        let origin = Vec2::from(layout.position);
+        commands
+            .spawn_bundle(SpriteBundle {
+                material: materials.add(ColorMaterial::color(layout.kind.color())),
+                transform: Transform::from_xyz(origin.x, origin.y, 1.0),
+                sprite: Sprite::new(Vec2::from(layout.size)),
+                ..Default::default()
+            })
+            .insert(Obstacle {
+                kind: layout.kind,
+                origin,
+                motion: layout.motion,
+                elapsed: 0.0,
+            });
+    }
+}
+
+fn move_obstacles(
+    clock: Res<GameClock>,
+    game_running: Res<GameState>,
+    mut obstacles: Query<(&mut Obstacle, &mut Transform)>,
+) {
+    if game_running.is_not_running() {
+        return;
+    }
+
+    for (mut obstacle, mut transform) in obstacles.iter_mut() {
+        let motion = match obstacle.motion {
+            Some(motion) if motion.period > 0.0 => motion,
+            _ => continue,
+        };
+
+        obstacle.elapsed = (obstacle.elapsed + clock.delta_seconds()) % motion.period;
+        let swing = (obstacle.elapsed / motion.period * std::f32::consts::TAU).sin();
+        let position = obstacle.origin + Vec2::from(motion.offset) * swing;
+
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+    }
+}
+
+fn deflect_falling_objects(
+    game_running: Res<GameState>,
+    obstacles: Query<(&Obstacle, &Transform, &Sprite), Without<ObjectKind>>,
+    mut falling_objects: Query<(&mut Transform, &mut Drift, &Sprite), With<ObjectKind>>,
+) {
+    if game_running.is_not_running() {
+        return;
+    }
+
+    for (obstacle, obstacle_transform, obstacle_sprite) in obstacles.iter() {
+        for (mut transform, mut drift, sprite) in falling_objects.iter_mut() {
+            let collision = collide(
+                obstacle_transform.translation,
+                obstacle_sprite.size,
+                transform.translation,
+                sprite.size,
+            );
+
+            let direction = if transform.translation.x < obstacle_transform.translation.x {
+                -1.0
+            } else {
+                1.0
+            };
+
+            match collision {
+                // The object landed on top of the obstacle, keep it there while it slides off
+                Some(Collision::Bottom) => {
+                    transform.translation.y = obstacle_transform.translation.y
+                        + (obstacle_sprite.size.y + sprite.size.y) / 2.0;
+                    drift.0 = direction * obstacle.kind.deflection();
+                }
+                Some(Collision::Left) | Some(Collision::Right) | Some(Collision::Top) => {
+                    drift.0 = direction * obstacle.kind.deflection();
+                }
+                None => (),
+            }
+        }
+    }
+}
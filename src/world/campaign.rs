@@ -0,0 +1,344 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use super::{
+    arena::{Arena, HudAnchor},
+    falling_object::{SpawnTable, SpawnWeight, Speed},
+    mode::CurrentMode,
+    player::Player,
+    scoreboard::{Lives, Misses, Scoreboard},
+    GameOver, GameStarted, GameState, NEW_GAME_LABEL, PLAYER_DEATH_LABEL,
+};
+use crate::{
+    embedded::AssetSource,
+    fonts::Fonts,
+    localization::Localization,
+    settings::{GameMode, Settings},
+    storage,
+};
+
+/// Plays the levels of `assets/levels/campaign.toml` in order, and remembers how far the
+/// player got.
+pub(super) struct CampaignPlugin;
+
+impl Plugin for CampaignPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let campaign = Campaign::load(app.world().get_resource::<AssetSource>().unwrap());
+        app.insert_resource(campaign);
+        app.insert_resource(CampaignProgress::load_from_file());
+        app.insert_resource(LevelState::default());
+
+        app.add_startup_system(spawn_level_label.system());
+        app.add_system(
+            start_level
+                .system()
+                .after(NEW_GAME_LABEL)
+                .before(PLAYER_DEATH_LABEL),
+        );
+        app.add_system(check_goal.system().before(PLAYER_DEATH_LABEL));
+        app.add_system(finish_level.system().after(PLAYER_DEATH_LABEL));
+        app.add_system(update_level_label.system());
+    }
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct Campaign {
+    pub levels: Vec<Level>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct Level {
+    /// Titles are looked up as `level-<name>`
+    pub name: String,
+    pub goal: Goal,
+    #[serde(default = "classic_arena")]
+    pub arena: String,
+    /// Keeps the speed from going up as stars get caught
+    pub speed: Option<f32>,
+    #[serde(default)]
+    pub spawns: Vec<SpawnWeight>,
+}
+
+fn classic_arena() -> String {
+    "classic".to_string()
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Goal {
+    Catch {
+        stars: u64,
+        #[serde(default)]
+        without_missing: bool,
+    },
+    Survive {
+        seconds: f32,
+    },
+}
+
+impl Campaign {
+    pub fn load(source: &AssetSource) -> Self {
+        let path = PathBuf::from("levels").join("campaign.toml");
+
+        let contents = match source.read_to_string(&path) {
+            Some(contents) => contents,
+            None => {
+                warn!("Could not load {}", path.display());
+                return Self::default();
+            }
+        };
+
+        match toml::from_str(&contents) {
+            Ok(campaign) => campaign,
+            Err(error) => {
+                warn!("Could not load the levels in {}: {}", path.display(), error);
+                Self::default()
+            }
+        }
+    }
+}
+
+/// How many levels have been completed, stored as `campaign.toml` in the user's data
+/// directory.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CampaignProgress {
+    pub completed: usize,
+}
+
+impl CampaignProgress {
+    pub fn load_from_file() -> Self {
+        storage::load_toml(storage::data_file("campaign.toml"), "campaign progress")
+    }
+
+    pub fn update_file(&self) {
+        storage::save_toml(
+            storage::data_file("campaign.toml"),
+            "campaign progress",
+            self,
+        );
+    }
+
+    /// The first level which hasn't been completed. Once all of them have, the last one can be
+    /// played again.
+    pub fn next_level(&self, campaign: &Campaign) -> Option<usize> {
+        match campaign.levels.len() {
+            0 => None,
+            count => Some(self.completed.min(count - 1)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Won,
+    Lost,
+}
+
+/// The level being played, and how it ended.
+#[derive(Default)]
+pub struct LevelState {
+    pub playing: Option<usize>,
+    pub outcome: Option<Outcome>,
+}
+
+fn start_level(
+    mut game_started: EventReader<GameStarted>,
+    campaign: Res<Campaign>,
+    progress: Res<CampaignProgress>,
+    settings: Res<Settings>,
+    source: Res<AssetSource>,
+    mut level_state: ResMut<LevelState>,
+    mut current_mode: ResMut<CurrentMode>,
+    mut speed: ResMut<Speed>,
+    mut spawn_table: ResMut<SpawnTable>,
+    mut arena: ResMut<Arena>,
+    mut players: Query<(&Player, &mut Transform)>,
+) {
+    if game_started.iter().next().is_none() {
+        return;
+    }
+
+    *level_state = LevelState::default();
+    let level = match progress.next_level(&campaign) {
+        Some(index) if current_mode.mode == GameMode::Campaign => {
+            level_state.playing = Some(index);
+            Some(&campaign.levels[index])
+        }
+        _ => None,
+    };
+
+    let arena_name = level.map_or(&settings.arena, |level| &level.arena);
+    if arena.name != *arena_name {
+        *arena = Arena::load(&source, arena_name);
+        let count = players.iter_mut().count();
+        for (player, mut transform) in players.iter_mut() {
+            let spawn = arena.player_spawn(player.0, count);
+            transform.translation.x = spawn.x;
+            transform.translation.y = spawn.y;
+        }
+    }
+
+    let level = match level {
+        Some(level) => level,
+        None => {
+            *spawn_table = SpawnTable::default();
+            return;
+        }
+    };
+
+    *spawn_table = SpawnTable(level.spawns.clone());
+    if let Some(level_speed) = level.speed {
+        speed.set(level_speed);
+        current_mode.fix_speed();
+    }
+    if let Goal::Survive { seconds } = level.goal {
+        current_mode.set_time_limit(seconds);
+    }
+}
+
+/// Ends levels as soon as their goal is reached, or can't be reached anymore.
+fn check_goal(
+    game_state: Res<GameState>,
+    campaign: Res<Campaign>,
+    mut level_state: ResMut<LevelState>,
+    mut current_mode: ResMut<CurrentMode>,
+    players: Query<(&Scoreboard, &Misses), With<Player>>,
+) {
+    let index = match level_state.playing {
+        Some(index) if !game_state.is_not_running() && level_state.outcome.is_none() => index,
+        _ => return,
+    };
+
+    if let Goal::Catch {
+        stars,
+        without_missing,
+    } = campaign.levels[index].goal
+    {
+        let best_score = players.iter().map(|(s, _)| s.score()).max().unwrap_or(0);
+        let missed = players.iter().any(|(_, misses)| misses.0 > 0);
+
+        if best_score >= stars {
+            level_state.outcome = Some(Outcome::Won);
+            current_mode.finish();
+        } else if without_missing && missed {
+            level_state.outcome = Some(Outcome::Lost);
+            current_mode.finish();
+        }
+    }
+}
+
+fn finish_level(
+    mut game_over: EventReader<GameOver>,
+    campaign: Res<Campaign>,
+    mut progress: ResMut<CampaignProgress>,
+    mut level_state: ResMut<LevelState>,
+    lives: Query<&Lives, With<Player>>,
+) {
+    if game_over.iter().next().is_none() {
+        return;
+    }
+
+    let index = match level_state.playing {
+        Some(index) => index,
+        None => return,
+    };
+
+    // Surviving levels end when the time runs out, with someone still standing
+    let outcome = level_state
+        .outcome
+        .unwrap_or_else(|| match campaign.levels[index].goal {
+            Goal::Survive { .. } if lives.iter().any(|lives| !lives.is_dead()) => Outcome::Won,
+            _ => Outcome::Lost,
+        });
+    level_state.outcome = Some(outcome);
+
+    if outcome == Outcome::Won && index == progress.completed {
+        progress.completed += 1;
+        progress.update_file();
+    }
+}
+
+struct LevelLabel;
+
+fn spawn_level_label(mut commands: Commands, fonts: Res<Fonts>) {
+    commands
+        .spawn_bundle(TextBundle {
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: fonts.ui.clone(),
+                    font_size: 20.0,
+                    color: Color::rgb(0.0, 0.0, 0.0),
+                },
+                Default::default(),
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(LevelLabel)
+        .insert(HudAnchor::bottom(5.0, 60.0).with_font_size(20.0));
+}
+
+fn update_level_label(
+    campaign: Res<Campaign>,
+    progress: Res<CampaignProgress>,
+    level_state: Res<LevelState>,
+    settings: Res<Settings>,
+    localization: Res<Localization>,
+    mut labels: Query<&mut Text, With<LevelLabel>>,
+) {
+    // Before a level starts, show the one which is up next
+    let index = match (level_state.playing, settings.mode) {
+        (Some(index), _) => Some(index),
+        (None, GameMode::Campaign) => progress.next_level(&campaign),
+        (None, _) => None,
+    };
+
+    let value = match index {
+        Some(index) => {
+            let level = &campaign.levels[index];
+            let goal = match level.goal {
+                Goal::Catch {
+                    stars,
+                    without_missing,
+                } => localization.format(
+                    if without_missing {
+                        "goal-catch-without-missing"
+                    } else {
+                        "goal-catch"
+                    },
+                    &[("stars", stars.to_string())],
+                ),
+                Goal::Survive { seconds } => {
+                    localization.format("goal-survive", &[("seconds", seconds.to_string())])
+                }
+            };
+            let status = match level_state.outcome {
+                Some(Outcome::Won) => localization.get("level-won"),
+                Some(Outcome::Lost) => localization.get("level-lost"),
+                None => goal,
+            };
+            localization.format(
+                "level-label",
+                &[
+                    ("number", (index + 1).to_string()),
+                    ("title", localization.get(&format!("level-{}", level.name))),
+                    ("status", status),
+                ],
+            )
+        }
+        None => String::new(),
+    };
+
+    for mut text in labels.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use bevy::prelude::*;
+
+use super::{arena::HudAnchor, replay::GameClock, GameState};
+use crate::fonts::Fonts;
+
+/// Seconds counted down before a game starts, or resumes.
+const COUNTDOWN_SECONDS: f32 = 3.0;
+
+/// Counts 3, 2, 1 before the game gets going, so that the player has time to get ready.
+pub(super) struct CountdownPlugin;
+
+impl Plugin for CountdownPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(Countdown::default());
+        app.add_startup_system(spawn_countdown_label.system());
+        app.add_system(count_down.system());
+        app.add_system(show_countdown.system());
+    }
+}
+
+pub struct Countdown {
+    time_left: f32,
+}
+
+impl Default for Countdown {
+    fn default() -> Self {
+        Self {
+            time_left: COUNTDOWN_SECONDS,
+        }
+    }
+}
+
+impl Countdown {
+    pub fn restart(&mut self) {
+        self.time_left = COUNTDOWN_SECONDS;
+    }
+}
+
+fn count_down(
+    clock: Res<GameClock>,
+    mut game_state: ResMut<GameState>,
+    mut countdown: ResMut<Countdown>,
+) {
+    if *game_state != GameState::CountingDown {
+        return;
+    }
+
+    countdown.time_left -= clock.delta_seconds();
+    if countdown.time_left <= 0.0 {
+        *game_state = GameState::Running;
+    }
+}
+
+struct CountdownLabel;
+
+fn spawn_countdown_label(mut commands: Commands, fonts: Res<Fonts>) {
+    commands
+        .spawn_bundle(TextBundle {
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: fonts.ui.clone(),
+                    font_size: 96.0,
+                    color: Color::rgb_u8(46, 110, 180),
+                },
+                Default::default(),
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                ..Default::default()
+            },
+            visible: Visible {
+                is_visible: false,
+                is_transparent: true,
+            },
+            ..Default::default()
+        })
+        .insert(CountdownLabel)
+        .insert(HudAnchor::top(220.0, 320.0).with_font_size(96.0));
+}
+
+fn show_countdown(
+    game_state: Res<GameState>,
+    countdown: Res<Countdown>,
+    mut labels: Query<(&mut Text, &mut Visible), With<CountdownLabel>>,
+) {
+    let counting_down = *game_state == GameState::CountingDown;
+    let value = (countdown.time_left.ceil() as u32).max(1).to_string();
+    for (mut text, mut visible) in labels.iter_mut() {
+        if visible.is_visible != counting_down {
+            visible.is_visible = counting_down;
+        }
+        if counting_down && text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use super::{
+    falling_object::SpawnRng,
+    mode::CurrentMode,
+    player::Player,
+    replay::{Recording, Replay},
+    scoreboard::Scoreboard,
+    GameOver, GameStarted, NEW_GAME_LABEL, PLAYER_DEATH_LABEL,
+};
+use crate::{settings::GameMode, storage};
+
+/// Gives daily games the seed of the day and keeps track of their scores.
+pub(super) struct DailyPlugin;
+
+impl Plugin for DailyPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(DailyHistory::load_from_file());
+        app.insert_resource(DailyAttempt(None));
+        app.add_system(
+            start_daily_game
+                .system()
+                .after(NEW_GAME_LABEL)
+                .before(PLAYER_DEATH_LABEL),
+        );
+        app.add_system(record_daily_score.system().after(PLAYER_DEATH_LABEL));
+    }
+}
+
+/// A day, counted in UTC so that everyone gets the same one at the same time.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Day(u64);
+
+impl Day {
+    pub fn today() -> Self {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since_epoch| since_epoch.as_secs());
+        Self(seconds / (24 * 60 * 60))
+    }
+
+    pub fn seed(self) -> u64 {
+        // Spread consecutive days apart, their seeds shouldn't look alike
+        self.0.wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ 0x5354_4152_4849_4748
+    }
+
+    /// The date as `YYYY-MM-DD`.
+    pub fn date(self) -> String {
+        // Converts days since 1970-01-01 to a civil date, following
+        // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let z = self.0 as i64 + 719_468;
+        let era = z.div_euclid(146_097);
+        let day_of_era = z.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        format!("{:04}-{:02}-{:02}", year, month, day)
+    }
+}
+
+/// The score of the scored attempt of every day played, stored as `daily.toml` in the
+/// user's data directory.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DailyHistory {
+    best: BTreeMap<String, u64>,
+}
+
+impl DailyHistory {
+    pub fn load_from_file() -> Self {
+        storage::load_toml(storage::data_file("daily.toml"), "daily scores")
+    }
+
+    pub fn update_file(&self) {
+        storage::save_toml(storage::data_file("daily.toml"), "daily scores", self);
+    }
+
+    pub fn best(&self, day: Day) -> Option<u64> {
+        self.best.get(&day.date()).copied()
+    }
+
+    pub fn has_played(&self, day: Day) -> bool {
+        self.best(day).is_some()
+    }
+
+    fn record(&mut self, day: Day, score: u64) {
+        let best = self.best.entry(day.date()).or_insert(0);
+        *best = (*best).max(score);
+    }
+}
+
+/// The day of the scored daily game being played, if there is one.
+struct DailyAttempt(Option<Day>);
+
+fn start_daily_game(
+    mut game_started: EventReader<GameStarted>,
+    mut current_mode: ResMut<CurrentMode>,
+    mut spawn_rng: ResMut<SpawnRng>,
+    mut history: ResMut<DailyHistory>,
+    mut attempt: ResMut<DailyAttempt>,
+    mut recording: ResMut<Recording>,
+    replay: Option<Res<Replay>>,
+) {
+    if game_started.iter().next().is_none() {
+        return;
+    }
+
+    attempt.0 = None;
+    if current_mode.mode != GameMode::Daily {
+        return;
+    }
+
+    let today = Day::today();
+    // Replays of daily games play out on the day they were recorded on
+    let seed = replay
+        .as_ref()
+        .and_then(|replay| replay.daily_seed)
+        .unwrap_or_else(|| today.seed());
+    spawn_rng.restart_with(seed);
+    recording.record_daily_seed(seed);
+
+    // Watching or testing a daily game doesn't use up today's attempt
+    if replay.is_some() {
+        return;
+    }
+
+    if history.has_played(today) {
+        current_mode.make_practice();
+        return;
+    }
+
+    // The attempt counts as soon as it starts, quitting halfway doesn't give another one
+    history.record(today, 0);
+    history.update_file();
+    attempt.0 = Some(today);
+}
+
+fn record_daily_score(
+    mut game_over: EventReader<GameOver>,
+    scoreboards: Query<&Scoreboard, With<Player>>,
+    mut history: ResMut<DailyHistory>,
+    mut attempt: ResMut<DailyAttempt>,
+    replay: Option<Res<Replay>>,
+) {
+    if game_over.iter().next().is_none() || replay.is_some() {
+        return;
+    }
+
+    if let Some(day) = attempt.0.take() {
+        let best_score = scoreboards.iter().map(|s| s.score()).max().unwrap_or(0);
+        history.record(day, best_score);
+        history.update_file();
+    }
+}
@@ -1,29 +1,59 @@
+mod registry;
+
+pub use registry::*;
+
 use bevy::{
-    core::FixedTimestep,
+    ecs::schedule::ShouldRun,
     prelude::*,
     sprite::collide_aabb::{collide, Collision},
 };
 
 use super::{
-    scoreboard::{Lives, Scoreboard},
-    GameState, Wall, PLAYER_DEATH_LABEL,
+    arena::{Arena, Wall},
+    mode::CurrentMode,
+    player::{take_life, Invulnerable, Player},
+    replay::GameClock,
+    scoreboard::{Combo, Lives, Misses, Scoreboard},
+    theme::Theme,
+    EffectRequested, GameState, ObjectMissed, PlayerDied, PLAYER_DEATH_LABEL,
+};
+use crate::{
+    embedded::AssetSource,
+    settings::{Difficulty, Settings},
 };
+use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
+use serde::Deserialize;
+
+pub(super) static FALLING_OBJECT_GRAVITY_LABEL: &str = "falling_object_gravity_system";
+
+/// Seconds between two falling objects.
+const SPAWN_INTERVAL: f64 = 2.0;
 
 pub(super) struct StarPlugin;
 
 impl Plugin for StarPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.insert_resource(Speed {
-            last_increased_at: 0,
-            value: 1.0,
-        });
+        let difficulty = app.world().get_resource::<Settings>().unwrap().difficulty;
+        app.insert_resource(Speed::new(difficulty));
+        let seed = match app.world().get_resource::<Seed>() {
+            Some(seed) => seed.0,
+            None => thread_rng().gen(),
+        };
+        app.insert_resource(SpawnRng::new(seed));
+        app.insert_resource(SpawnTable::default());
+        let registry = ObjectRegistry::load(app.world().get_resource::<AssetSource>().unwrap());
+        app.insert_resource(registry);
 
         app.add_system_set(
             SystemSet::new()
-                .with_run_criteria(FixedTimestep::steps_per_second(0.5))
+                .with_run_criteria(spawn_step.system())
                 .with_system(spawn_falling_object.system()),
         );
-        app.add_system(falling_object_gravity.system());
+        app.add_system(
+            falling_object_gravity
+                .system()
+                .label(FALLING_OBJECT_GRAVITY_LABEL),
+        );
         app.add_system(
             falling_object_wall_collision
                 .system()
@@ -33,102 +63,293 @@ impl Plugin for StarPlugin {
 }
 
 pub struct Speed {
+    /// What the game was started on
+    difficulty: Difficulty,
     last_increased_at: u64,
     value: f32,
+    /// Added on top of the speed for as long as an event lasts
+    boost: f32,
 }
 
 impl Speed {
-    pub fn increase(&mut self) {
+    pub fn new(difficulty: Difficulty) -> Self {
+        Self {
+            difficulty,
+            last_increased_at: 0,
+            value: difficulty.initial_speed(),
+            boost: 0.0,
+        }
+    }
+
+    /// Speeds up once for every multiple of 10 the score went past on its way from `from` to
+    /// `to`, however many points were added at once.
+    pub fn increase_between(&mut self, from: u64, to: u64) {
+        let first = from.saturating_add(1).max(self.last_increased_at);
+        if to < first {
+            return;
+        }
+
+        let milestones = to / 10 - (first - 1) / 10;
+        self.value += self.step() * milestones as f32;
+    }
+
+    fn step(&self) -> f32 {
         if (0..100).contains(&self.last_increased_at) {
-            self.value += 0.25;
+            0.25
         } else if (100..200).contains(&self.last_increased_at) {
-            self.value += 0.5;
+            0.5
         } else if (200..350).contains(&self.last_increased_at) {
-            self.value += 0.75;
+            0.75
         } else if (350..500).contains(&self.last_increased_at) {
-            self.value += 0.8;
+            0.8
         } else {
-            self.value += 1.0;
+            1.0
         }
     }
 
-    pub fn should_increase(&self, scoreboard: Scoreboard) -> bool {
-        scoreboard.score() % 10 == 0 && scoreboard.score() >= self.last_increased_at
+    pub fn difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+
+    pub fn value(&self) -> f32 {
+        (self.value + self.boost).max(0.25)
+    }
+
+    /// The speed the game got to by itself, without what events add on top.
+    pub fn base_value(&self) -> f32 {
+        self.value
+    }
+
+    /// Speeds objects up, or down, until the boost is taken back with the opposite amount.
+    pub fn boost(&mut self, amount: f32) {
+        self.boost += amount;
+    }
+
+    /// Sets the speed outright, for levels played at a fixed one.
+    pub fn set(&mut self, value: f32) {
+        self.value = value.max(0.25);
+    }
+
+    /// Changes the speed by `amount`, without letting objects stop falling.
+    pub fn change(&mut self, amount: f32) {
+        self.value = (self.value + amount).max(0.25);
+    }
+}
+
+/// Seeds [`SpawnRng`], picked at random unless inserted before the plugins are added.
+pub struct Seed(pub u64);
+
+/// Decides where, and what kind of, objects get spawned. Every game starts over from the same
+/// seed, so games can be repeated exactly.
+pub struct SpawnRng {
+    seed: u64,
+    /// The seed the current game started from
+    run_seed: u64,
+    rng: StdRng,
+}
+
+impl SpawnRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            run_seed: seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The seed the current game started from, which differs from [`SpawnRng::seed`] in
+    /// modes which pick their own.
+    pub fn run_seed(&self) -> u64 {
+        self.run_seed
+    }
+
+    /// For everything else that has to play out the same way with the same seed.
+    pub fn rng(&mut self) -> &mut StdRng {
+        &mut self.rng
+    }
+
+    pub fn restart(&mut self) {
+        self.restart_with(self.seed);
     }
+
+    /// Starts over from another seed, for modes which pick their own. Later games go back to
+    /// the original one.
+    pub fn restart_with(&mut self, seed: u64) {
+        self.run_seed = seed;
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+}
+
+/// Overrides which objects get spawned, and how often. Empty unless a level or an event sets
+/// it, in which case the classic rules apply: stars, and once in a while a heart once someone
+/// has 50 points.
+#[derive(Clone, Default, Deserialize)]
+pub struct SpawnTable(pub Vec<SpawnWeight>);
+
+#[derive(Clone, Deserialize)]
+pub struct SpawnWeight {
+    /// The name of the object, see `assets/objects`
+    pub kind: String,
+    pub weight: u32,
 }
 
-pub enum ObjectKind {
-    Star,
-    Heart,
+impl SpawnTable {
+    /// Objects missing from the registry never get picked.
+    fn pick(&self, rng: &mut StdRng, registry: &ObjectRegistry) -> Option<ObjectKind> {
+        let entries: Vec<(ObjectKind, u64)> = self
+            .0
+            .iter()
+            .filter_map(|entry| Some((registry.find(&entry.kind)?, u64::from(entry.weight))))
+            .collect();
+        let total: u64 = entries.iter().map(|(_, weight)| weight).sum();
+        if total == 0 {
+            return None;
+        }
+
+        let mut roll = roll_below(rng, total);
+        for (kind, weight) in entries {
+            if roll < weight {
+                return Some(kind);
+            }
+            roll -= weight;
+        }
+        None
+    }
+}
+
+/// Sideways movement picked up from bouncing off of obstacles.
+pub struct Drift(pub f32);
+
+/// Works like a `FixedTimestep`, except that only the time spent playing counts, so pausing
+/// the game also pauses the countdown to the next object.
+fn spawn_step(
+    clock: Res<GameClock>,
+    game_running: Res<GameState>,
+    mut elapsed: Local<f64>,
+) -> ShouldRun {
+    if game_running.is_not_running() {
+        return ShouldRun::No;
+    }
+
+    *elapsed += f64::from(clock.delta_seconds());
+    if *elapsed >= SPAWN_INTERVAL {
+        *elapsed -= SPAWN_INTERVAL;
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
 }
 
 fn spawn_falling_object(
     mut commands: Commands,
     mut materials: ResMut<Assets<ColorMaterial>>,
     game_running: Res<GameState>,
-    scoreboard: Res<Scoreboard>,
+    scoreboards: Query<&Scoreboard, With<Player>>,
     asset_server: Res<AssetServer>,
+    theme: Res<Theme>,
+    registry: Res<ObjectRegistry>,
+    arena: Res<Arena>,
+    current_mode: Res<CurrentMode>,
+    spawn_table: Res<SpawnTable>,
+    mut spawn_rng: ResMut<SpawnRng>,
 ) {
     if game_running.is_not_running() {
         return;
     }
 
-    const SPAWN_Y: f32 = 260.0;
+    let spawn_y = arena.spawn_y;
 
-    use rand::{thread_rng, Rng};
-    let mut rng = thread_rng();
-    let x = rng.gen_range(-120.0..180.0);
+    let rng = &mut spawn_rng.rng;
+    let x = rng.gen_range(arena.spawn_x_range());
 
-    if scoreboard.score() >= 50 {
-        let spawn_heart = {
-            let x = rng.gen_range(1..100);
-            x == 5
-        };
-        if spawn_heart {
-            commands
-                .spawn_bundle(SpriteBundle {
-                    material: materials.add(asset_server.load("heart.png").into()),
-                    transform: Transform::from_xyz(x, SPAWN_Y, 0.0),
-                    sprite: Sprite::new(Vec2::new(40.0, 40.0)),
-                    ..Default::default()
-                })
-                .insert(ObjectKind::Heart);
-            return;
+    let kind = spawn_table.pick(rng, &registry).unwrap_or_else(|| {
+        // Hearts are of no use when lives don't run out
+        let best_score = scoreboards.iter().map(|s| s.score()).max().unwrap_or(0);
+        if best_score >= 50 && current_mode.has_lives() && rng.gen_range(1..100) == 5 {
+            ObjectKind::HEART
+        } else {
+            registry.pick_instead_of_star(rng)
         }
-    }
+    });
 
+    spawn_object(
+        &mut commands,
+        &mut materials,
+        &asset_server,
+        &theme,
+        &registry,
+        kind,
+        Vec2::new(x, spawn_y),
+    );
+}
+
+pub(super) fn spawn_object(
+    commands: &mut Commands,
+    materials: &mut Assets<ColorMaterial>,
+    asset_server: &AssetServer,
+    theme: &Theme,
+    registry: &ObjectRegistry,
+    kind: ObjectKind,
+    position: Vec2,
+) {
+    let [width, height] = registry.get(kind).size;
     commands
         .spawn_bundle(SpriteBundle {
-            material: materials.add(asset_server.load("star.png").into()),
-            transform: Transform::from_xyz(x, SPAWN_Y, 0.0),
-            sprite: Sprite::new(Vec2::new(40.0, 40.0)),
+            material: materials.add(asset_server.load(registry.sprite(kind, theme)).into()),
+            transform: Transform::from_xyz(position.x, position.y, 0.0),
+            sprite: Sprite::new(Vec2::new(width, height)),
             ..Default::default()
         })
-        .insert(ObjectKind::Star);
+        .insert(kind)
+        .insert(Drift(0.0));
 }
 
 fn falling_object_gravity(
-    mut falling_objects: Query<&mut Transform, With<ObjectKind>>,
+    mut falling_objects: Query<(&mut Transform, &Drift, &ObjectKind)>,
     game_running: Res<GameState>,
     speed: Res<Speed>,
+    registry: Res<ObjectRegistry>,
 ) {
     if game_running.is_not_running() {
         return;
     }
 
-    for mut star_transform in falling_objects.iter_mut() {
-        star_transform.translation.y -= speed.value;
+    for (mut star_transform, drift, kind) in falling_objects.iter_mut() {
+        star_transform.translation.y -= speed.value() * registry.get(*kind).fall_speed;
+        star_transform.translation.x += drift.0;
     }
 }
-fn falling_object_wall_collision(
+pub(super) fn falling_object_wall_collision(
     mut commands: Commands,
-    mut lives: ResMut<Lives>,
-    mut scoreboard: ResMut<Scoreboard>,
-    falling_objects: Query<(Entity, &Transform, &Sprite, &ObjectKind)>,
+    game_running: Res<GameState>,
+    current_mode: Res<CurrentMode>,
+    settings: Res<Settings>,
+    mut object_missed: EventWriter<ObjectMissed>,
+    registry: Res<ObjectRegistry>,
+    mut player_died: EventWriter<PlayerDied>,
+    mut effect_requests: EventWriter<EffectRequested>,
+    mut falling_objects: Query<(Entity, &Transform, &Sprite, &ObjectKind, &mut Drift)>,
+    mut players: Query<(
+        &Player,
+        &Transform,
+        &mut Scoreboard,
+        &mut Lives,
+        &mut Invulnerable,
+        &mut Misses,
+        &mut Combo,
+    )>,
     wall_query: Query<(&Transform, &Sprite), With<Wall>>,
 ) {
+    if game_running.is_not_running() {
+        return;
+    }
+
     for (wall_transform, wall_sprite) in wall_query.iter() {
-        for (entity, transform, sprite, kind) in falling_objects.iter() {
+        for (entity, transform, sprite, kind, mut drift) in falling_objects.iter_mut() {
             let collision = collide(
                 wall_transform.translation,
                 wall_sprite.size,
@@ -139,18 +360,68 @@ fn falling_object_wall_collision(
             let player_missed_object = match collision {
                 Some(c) => match c {
                     Collision::Bottom => true,
-                    _ => false,
+                    // Objects only reach the side walls after being deflected, bounce them back
+                    Collision::Left => {
+                        drift.0 = drift.0.abs();
+                        false
+                    }
+                    Collision::Right => {
+                        drift.0 = -drift.0.abs();
+                        false
+                    }
+                    Collision::Top => false,
                 },
                 None => false,
             };
 
             if player_missed_object {
-                match kind {
-                    ObjectKind::Star => {
-                        lives.remove_life();
-                    }
-                    ObjectKind::Heart => {
-                        scoreboard.remove_point();
+                // The miss counts against whoever was closest to the object
+                let x = transform.translation.x;
+                let player = players
+                    .iter_mut()
+                    .filter(|(_, _, _, lives, _, _, _)| !lives.is_dead())
+                    .min_by(|(_, a, _, _, _, _, _), (_, b, _, _, _, _, _)| {
+                        let distance_a = (a.translation.x - x).abs();
+                        let distance_b = (b.translation.x - x).abs();
+                        distance_a.partial_cmp(&distance_b).unwrap()
+                    });
+
+                if let Some((
+                    player,
+                    _,
+                    mut scoreboard,
+                    mut lives,
+                    mut invulnerable,
+                    mut misses,
+                    mut combo,
+                )) = player
+                {
+                    object_missed.send(ObjectMissed {
+                        player: player.0,
+                        kind: *kind,
+                    });
+                    for effect in registry.get(*kind).on_miss.iter() {
+                        match *effect {
+                            Effect::AddPoints { points } => scoreboard.add_points(points),
+                            Effect::AddLife if !lives.is_full() => lives.add_life(),
+                            Effect::AddLife => (),
+                            Effect::RemoveLife => {
+                                if current_mode.has_lives() {
+                                    take_life(
+                                        player,
+                                        &mut lives,
+                                        &mut invulnerable,
+                                        settings.grace_period,
+                                        &mut player_died,
+                                    );
+                                }
+                                misses.0 += 1;
+                                combo.break_off();
+                            }
+                            Effect::ApplyEffect { effect } => {
+                                effect_requests.send(EffectRequested(effect))
+                            }
+                        }
                     }
                 }
                 commands.entity(entity).despawn();
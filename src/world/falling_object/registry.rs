@@ -0,0 +1,192 @@
+use std::{
+    convert::TryFrom,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::{
+    embedded::AssetSource,
+    world::{scripted_event::ScriptedEvent, theme::Theme},
+};
+
+/// Which kind of falling object something is, out of the [`ObjectRegistry`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ObjectKind(usize);
+
+impl ObjectKind {
+    /// The registry always starts with the built-in kinds, so they can be named in code.
+    pub const STAR: Self = Self(0);
+    pub const HEART: Self = Self(1);
+}
+
+/// Something which happens when an object is caught, or missed.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Effect {
+    AddPoints { points: i64 },
+    AddLife,
+    RemoveLife,
+    ApplyEffect { effect: ScriptedEvent },
+}
+
+/// A kind of falling object, read from `assets/objects/<name>.toml`.
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct ObjectDefinition {
+    pub sprite: String,
+    pub size: [f32; 2],
+    /// How often the object falls, against the star's weight
+    pub spawn_weight: u32,
+    /// Multiplies how fast the object falls
+    pub fall_speed: f32,
+    pub on_catch: Vec<Effect>,
+    pub on_miss: Vec<Effect>,
+    /// The name the definition was loaded by
+    #[serde(skip)]
+    pub name: String,
+}
+
+impl Default for ObjectDefinition {
+    fn default() -> Self {
+        Self {
+            sprite: "star.png".to_string(),
+            size: [40.0, 40.0],
+            spawn_weight: 0,
+            fall_speed: 1.0,
+            on_catch: Vec::new(),
+            on_miss: Vec::new(),
+            name: String::new(),
+        }
+    }
+}
+
+impl ObjectDefinition {
+    fn load(source: &AssetSource, path: &Path) -> Option<Self> {
+        let name = path.file_stem()?.to_str()?.to_string();
+        let contents = match source.read_to_string(path) {
+            Some(contents) => contents,
+            None => {
+                warn!("Could not load {}", path.display());
+                return None;
+            }
+        };
+
+        match toml::from_str(&contents) {
+            Ok(definition) => Some(Self { name, ..definition }),
+            Err(error) => {
+                warn!("Could not load object {}: {}", path.display(), error);
+                None
+            }
+        }
+    }
+}
+
+/// Every kind of falling object, the built-in ones and those added by mods.
+pub struct ObjectRegistry {
+    definitions: Vec<ObjectDefinition>,
+}
+
+impl ObjectRegistry {
+    pub fn load(source: &AssetSource) -> Self {
+        let dir = PathBuf::from("objects");
+
+        // Whatever happens to their files, the built-in kinds have to be where their constants
+        // point to
+        let mut definitions: Vec<ObjectDefinition> = ["star", "heart"]
+            .iter()
+            .map(|name| {
+                let path = dir.join(name).with_extension("toml");
+                ObjectDefinition::load(source, &path).unwrap_or_else(|| ObjectDefinition {
+                    name: name.to_string(),
+                    ..Default::default()
+                })
+            })
+            .collect();
+
+        for path in source.read_dir(&dir) {
+            let is_manifest = path
+                .extension()
+                .map_or(false, |extension| extension == "toml");
+            let stem = path.file_stem().and_then(|stem| stem.to_str());
+            let known = definitions
+                .iter()
+                .any(|definition| Some(definition.name.as_str()) == stem);
+            if !is_manifest || known {
+                continue;
+            }
+
+            if let Some(definition) = ObjectDefinition::load(source, &path) {
+                info!("Loaded object {} from {}", definition.name, path.display());
+                definitions.push(definition);
+            }
+        }
+
+        Self { definitions }
+    }
+
+    pub fn get(&self, kind: ObjectKind) -> &ObjectDefinition {
+        &self.definitions[kind.0]
+    }
+
+    /// The kind called `name`, like `star`.
+    pub fn find(&self, name: &str) -> Option<ObjectKind> {
+        self.definitions
+            .iter()
+            .position(|definition| definition.name == name)
+            .map(ObjectKind)
+    }
+
+    /// Picks what falls instead of a star, which is a star unless the kinds added by mods win
+    /// out, as often as their weights say. No randomness gets used up without any of them, so
+    /// that games without mods play out the way they always have.
+    pub fn pick_instead_of_star(&self, rng: &mut impl Rng) -> ObjectKind {
+        let modded: Vec<(ObjectKind, u64)> = self
+            .definitions
+            .iter()
+            .enumerate()
+            .skip(2)
+            .filter(|(_, definition)| definition.spawn_weight > 0)
+            .map(|(index, definition)| (ObjectKind(index), u64::from(definition.spawn_weight)))
+            .collect();
+        if modded.is_empty() {
+            return ObjectKind::STAR;
+        }
+
+        let star = u64::from(self.get(ObjectKind::STAR).spawn_weight);
+        let total = star + modded.iter().map(|(_, weight)| weight).sum::<u64>();
+        let mut roll = roll_below(rng, total);
+        if roll < star {
+            return ObjectKind::STAR;
+        }
+        roll -= star;
+        for (kind, weight) in modded {
+            if roll < weight {
+                return kind;
+            }
+            roll -= weight;
+        }
+        ObjectKind::STAR
+    }
+
+    /// Stars and hearts look the way the theme wants them to.
+    pub fn sprite<'a>(&'a self, kind: ObjectKind, theme: &'a Theme) -> &'a str {
+        match kind {
+            ObjectKind::STAR => &theme.star,
+            ObjectKind::HEART => &theme.heart,
+            _ => &self.get(kind).sprite,
+        }
+    }
+}
+
+/// A random number below `total`, a sum of spawn weights. Totals which fit in a `u32`, as
+/// all of them used to, are rolled the way they always were, so that seeds keep playing out
+/// the same.
+pub fn roll_below(rng: &mut impl Rng, total: u64) -> u64 {
+    match u32::try_from(total) {
+        Ok(total) => u64::from(rng.gen_range(0..total)),
+        Err(_) => rng.gen_range(0..total),
+    }
+}
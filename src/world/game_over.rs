@@ -0,0 +1,295 @@
+use bevy::prelude::*;
+
+use super::{
+    achievements::AchievementsScreen,
+    any_screen_open,
+    arena::{ArenaViewport, HudAnchor},
+    falling_object::{SpawnRng, Speed},
+    mode::CurrentMode,
+    player::Player,
+    scoreboard::Combo,
+    stats::{format_time, GameStats, SessionStats, StatsScreen},
+    GameOver, GameStarted, StartRequested, PLAYER_DEATH_LABEL,
+};
+use crate::{
+    fonts::Fonts,
+    localization::Localization,
+    settings::{Difficulty, GameMode, SettingsScreen},
+};
+
+/// Sums up each game once it is over, offering to play again.
+pub(super) struct GameOverPlugin;
+
+impl Plugin for GameOverPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(GameOverScreen::default());
+        app.add_system(open_game_over_screen.system().after(PLAYER_DEATH_LABEL));
+        app.add_system(navigate_game_over_screen.system());
+        app.add_system(render_game_over_screen.system());
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Retry,
+    Menu,
+    ShareSeed,
+}
+
+static ACTIONS: [Action; 3] = [Action::Retry, Action::Menu, Action::ShareSeed];
+
+impl Action {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Retry => "game-over-retry",
+            Self::Menu => "game-over-menu",
+            Self::ShareSeed => "game-over-share-seed",
+        }
+    }
+}
+
+/// How the last game went.
+struct RunSummary {
+    score: u64,
+    new_best: bool,
+    stars_caught: u64,
+    stars_missed: u64,
+    seconds: f32,
+    max_speed: f32,
+    max_combo: u64,
+    run: PlayedRun,
+}
+
+/// What it takes to play the same game again, as it was when the game started.
+#[derive(Clone, Copy)]
+struct PlayedRun {
+    mode: GameMode,
+    difficulty: Difficulty,
+    seed: u64,
+}
+
+/// Shown from the end of a game until the next one starts, or the menu is picked.
+#[derive(Default)]
+struct GameOverScreen {
+    summary: Option<RunSummary>,
+    selected: usize,
+    seed_shared: bool,
+}
+
+fn open_game_over_screen(
+    mut game_started: EventReader<GameStarted>,
+    mut game_over: EventReader<GameOver>,
+    game: Res<GameStats>,
+    current_mode: Res<CurrentMode>,
+    speed: Res<Speed>,
+    spawn_rng: Res<SpawnRng>,
+    mut run: Local<Option<PlayedRun>>,
+    mut screen: ResMut<GameOverScreen>,
+    combos: Query<&Combo, With<Player>>,
+) {
+    // The settings may change during the game, and some modes pick their own seed or
+    // difficulty, what the game actually started with is what plays it again
+    if game_started.iter().next().is_some() {
+        *screen = GameOverScreen::default();
+        *run = Some(PlayedRun {
+            mode: current_mode.mode,
+            difficulty: speed.difficulty(),
+            seed: spawn_rng.run_seed(),
+        });
+    }
+
+    if let (Some(game_over), Some(run)) = (game_over.iter().next(), *run) {
+        *screen = GameOverScreen {
+            summary: Some(RunSummary {
+                score: game_over.score,
+                new_best: game_over.new_best,
+                stars_caught: game.stars_caught,
+                stars_missed: game.stars_missed,
+                seconds: game.seconds,
+                max_speed: game.peak_speed,
+                max_combo: combos.iter().map(|combo| combo.best).max().unwrap_or(0),
+                run,
+            }),
+            ..Default::default()
+        };
+    }
+}
+
+fn navigate_game_over_screen(
+    keyboard_input: Res<Input<KeyCode>>,
+    achievements_screen: Res<AchievementsScreen>,
+    stats_screen: Res<StatsScreen>,
+    mut settings_screen: ResMut<SettingsScreen>,
+    mut screen: ResMut<GameOverScreen>,
+    mut start_requests: EventWriter<StartRequested>,
+) {
+    if screen.summary.is_none()
+        || any_screen_open(&settings_screen, &achievements_screen, &stats_screen)
+    {
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Up) {
+        screen.selected = (screen.selected + ACTIONS.len() - 1) % ACTIONS.len();
+    } else if keyboard_input.just_pressed(KeyCode::Down) {
+        screen.selected = (screen.selected + 1) % ACTIONS.len();
+    } else if keyboard_input.just_pressed(KeyCode::Return) {
+        match ACTIONS[screen.selected] {
+            Action::Retry => start_requests.send(StartRequested),
+            // The settings are where the mode and difficulty of the next game get picked
+            Action::Menu => {
+                screen.summary = None;
+                settings_screen.toggle();
+            }
+            Action::ShareSeed => screen.seed_shared = true,
+        }
+    }
+}
+
+struct GameOverPanel;
+struct GameOverText;
+
+fn render_game_over_screen(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    screen: Res<GameOverScreen>,
+    session: Res<SessionStats>,
+    settings_screen: Res<SettingsScreen>,
+    achievements_screen: Res<AchievementsScreen>,
+    stats_screen: Res<StatsScreen>,
+    localization: Res<Localization>,
+    viewport: Res<ArenaViewport>,
+    fonts: Res<Fonts>,
+    panels: Query<Entity, With<GameOverPanel>>,
+    mut texts: Query<&mut Text, With<GameOverText>>,
+) {
+    let summary = match &screen.summary {
+        Some(summary)
+            if !any_screen_open(&settings_screen, &achievements_screen, &stats_screen) =>
+        {
+            summary
+        }
+        _ => {
+            for panel in panels.iter() {
+                commands.entity(panel).despawn_recursive();
+            }
+            return;
+        }
+    };
+
+    let needs_redraw = screen.is_changed()
+        || session.is_changed()
+        || localization.is_changed()
+        || viewport.is_changed()
+        || panels.iter().next().is_none();
+    if !needs_redraw {
+        return;
+    }
+
+    let font_size = 20.0 * viewport.pixels_per_unit;
+    let section = |value: String, color: Color| TextSection {
+        value,
+        style: TextStyle {
+            font: fonts.ui.clone(),
+            font_size,
+            color,
+        },
+    };
+    let black = Color::rgb(0.0, 0.0, 0.0);
+    let highlight = Color::rgb_u8(46, 110, 180);
+
+    let mut sections = vec![
+        section(localization.get("game-over-title") + "\n\n", black),
+        section(
+            localization.format("game-over-score", &[("score", summary.score.to_string())]) + "\n",
+            black,
+        ),
+    ];
+    if summary.new_best {
+        sections.push(section(
+            localization.get("game-over-new-best") + "\n",
+            highlight,
+        ));
+    }
+    let details = [
+        localization.format(
+            "game-over-caught-missed",
+            &[
+                ("caught", summary.stars_caught.to_string()),
+                ("missed", summary.stars_missed.to_string()),
+            ],
+        ),
+        localization.format("game-over-time", &[("time", format_time(summary.seconds))]),
+        localization.format(
+            "game-over-max-speed",
+            &[("speed", format!("{:.2}", summary.max_speed))],
+        ),
+        localization.format(
+            "game-over-max-combo",
+            &[("combo", summary.max_combo.to_string())],
+        ),
+        localization.format(
+            "game-over-session",
+            &[
+                ("games", session.games.to_string()),
+                ("best", session.best_score.to_string()),
+            ],
+        ),
+    ];
+    sections.push(section(details.join("\n") + "\n\n", black));
+
+    for (index, action) in ACTIONS.iter().enumerate() {
+        let color = if index == screen.selected {
+            highlight
+        } else {
+            black
+        };
+        sections.push(section(localization.get(action.label()) + "\n", color));
+    }
+    if screen.seed_shared {
+        sections.push(section(
+            localization.format(
+                "game-over-seed",
+                &[
+                    ("seed", summary.run.seed.to_string()),
+                    ("mode", summary.run.mode.name().to_string()),
+                    ("difficulty", summary.run.difficulty.name().to_string()),
+                ],
+            ) + "\n",
+            black,
+        ));
+    }
+    sections.push(section(
+        format!("\n{}", localization.get("game-over-hint")),
+        Color::rgb(0.3, 0.3, 0.3),
+    ));
+
+    if let Some(mut text) = texts.iter_mut().next() {
+        text.sections = sections;
+        return;
+    }
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                padding: Rect::all(Val::Px(10.0)),
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(1.0, 1.0, 1.0, 0.92).into()),
+            ..Default::default()
+        })
+        .insert(HudAnchor::top(70.0, 120.0).with_size(Vec2::new(400.0, 400.0)))
+        .insert(GameOverPanel)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text {
+                        sections,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .insert(GameOverText);
+        });
+}
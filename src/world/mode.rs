@@ -0,0 +1,166 @@
+use bevy::prelude::*;
+
+use super::{arena::HudAnchor, falling_object::Speed, replay::GameClock, GameState};
+use crate::{
+    fonts::Fonts,
+    localization::Localized,
+    settings::{GameMode, Settings},
+};
+
+pub(super) struct ModePlugin;
+
+impl Plugin for ModePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let mode = app.world().get_resource::<Settings>().unwrap().mode;
+        app.insert_resource(CurrentMode::new(mode));
+        app.add_startup_system(spawn_practice_label.system());
+        app.add_system(run_countdown.system());
+        app.add_system(change_speed.system());
+        app.add_system(show_practice_label.system());
+    }
+}
+
+/// The mode of the game being played, and what only some modes keep track of. Changing the
+/// mode in the settings only affects the next game.
+pub struct CurrentMode {
+    pub mode: GameMode,
+    practice: bool,
+    fixed_speed: bool,
+    finished: bool,
+    time_left: Option<f32>,
+}
+
+impl CurrentMode {
+    pub fn new(mode: GameMode) -> Self {
+        Self {
+            mode,
+            practice: mode.is_practice(),
+            fixed_speed: mode.has_manual_speed(),
+            finished: false,
+            time_left: mode.time_limit(),
+        }
+    }
+
+    pub fn has_lives(&self) -> bool {
+        self.mode.has_lives()
+    }
+
+    /// Whether the game doesn't count towards high scores.
+    pub fn is_practice(&self) -> bool {
+        self.practice
+    }
+
+    /// Turns a game which would have been scored into practice.
+    pub fn make_practice(&mut self) {
+        self.practice = true;
+    }
+
+    /// Whether the speed goes up as stars get caught.
+    pub fn has_automatic_speed(&self) -> bool {
+        !self.fixed_speed
+    }
+
+    pub fn fix_speed(&mut self) {
+        self.fixed_speed = true;
+    }
+
+    /// The seconds left in timed games.
+    pub fn time_left(&self) -> Option<f32> {
+        self.time_left
+    }
+
+    pub fn set_time_limit(&mut self, seconds: f32) {
+        self.time_left = Some(seconds);
+    }
+
+    /// Ends the game before the players run out of lives or time.
+    pub fn finish(&mut self) {
+        self.finished = true;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished || self.time_left == Some(0.0)
+    }
+}
+
+fn run_countdown(
+    clock: Res<GameClock>,
+    game_state: Res<GameState>,
+    mut current_mode: ResMut<CurrentMode>,
+) {
+    if game_state.is_not_running() {
+        return;
+    }
+
+    if let Some(time_left) = current_mode.time_left.as_mut() {
+        *time_left = (*time_left - clock.delta_seconds()).max(0.0);
+    }
+}
+
+/// Lets the speed be set by hand in practice games.
+pub(super) fn change_speed(
+    keyboard_input: Res<Input<KeyCode>>,
+    game_state: Res<GameState>,
+    current_mode: Res<CurrentMode>,
+    mut speed: ResMut<Speed>,
+) {
+    if game_state.is_not_running() || !current_mode.mode.has_manual_speed() {
+        return;
+    }
+
+    let faster = [KeyCode::Equals, KeyCode::Plus, KeyCode::NumpadAdd];
+    let slower = [KeyCode::Minus, KeyCode::NumpadSubtract];
+    if faster.iter().any(|key| keyboard_input.just_pressed(*key)) {
+        speed.change(0.25);
+    } else if slower.iter().any(|key| keyboard_input.just_pressed(*key)) {
+        speed.change(-0.25);
+    }
+}
+
+/// Marks practice games on the HUD, so they aren't mistaken for scored ones.
+struct PracticeLabel;
+
+fn practice_label(mode: GameMode) -> &'static str {
+    if mode.has_manual_speed() {
+        "mode-zen-hud"
+    } else {
+        "mode-practice-hud"
+    }
+}
+
+fn spawn_practice_label(mut commands: Commands, fonts: Res<Fonts>) {
+    commands
+        .spawn_bundle(TextBundle {
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: fonts.ui.clone(),
+                    font_size: 24.0,
+                    color: Color::rgb_u8(46, 110, 180),
+                },
+                Default::default(),
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(Localized("mode-zen-hud"))
+        .insert(PracticeLabel)
+        .insert(HudAnchor::top(5.0, 190.0).with_font_size(24.0));
+}
+
+fn show_practice_label(
+    current_mode: Res<CurrentMode>,
+    mut labels: Query<(&mut Visible, &mut Localized), With<PracticeLabel>>,
+) {
+    let message = practice_label(current_mode.mode);
+    for (mut visible, mut localized) in labels.iter_mut() {
+        visible.is_visible = current_mode.is_practice();
+        // Only replaced when different, so that the text isn't translated again every frame
+        if localized.0 != message {
+            localized.0 = message;
+        }
+    }
+}
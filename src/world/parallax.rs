@@ -0,0 +1,145 @@
+use bevy::prelude::*;
+use rand::{thread_rng, Rng};
+
+use super::{arena::Arena, falling_object::Speed, theme::Theme, GameState};
+use crate::settings::Settings;
+
+/// How deep the background is drawn, the layers go between it and everything else.
+pub const BACKGROUND_DEPTH: f32 = -1.0;
+/// How many times a second twinkling sprites fade in and out, roughly.
+const TWINKLE_SPEED: f32 = 2.5;
+
+/// Scrolls the layers of the theme over the background, faster as the game speeds up.
+pub(super) struct ParallaxPlugin;
+
+impl Plugin for ParallaxPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(spawn_layers.system());
+        app.add_system(scroll_layers.system());
+        app.add_system(twinkle.system());
+    }
+}
+
+/// One copy of the sprite of a [`ParallaxLayer`](super::theme::ParallaxLayer).
+struct ParallaxPiece {
+    layer: usize,
+    /// Where in its fading in and out a twinkling piece is
+    phase: f32,
+}
+
+/// Scatters the pieces of every layer over the arena, again whenever the theme changes.
+fn spawn_layers(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+    theme: Res<Theme>,
+    arena: Res<Arena>,
+    pieces: Query<Entity, With<ParallaxPiece>>,
+) {
+    if !theme.is_changed() {
+        return;
+    }
+
+    for piece in pieces.iter() {
+        commands.entity(piece).despawn();
+    }
+
+    let mut rng = thread_rng();
+    for (index, layer) in theme.layers.iter().enumerate() {
+        let texture = asset_server.load(layer.sprite.as_str());
+        let shared = materials.add(ColorMaterial::modulated_texture(
+            texture.clone(),
+            layer.color(),
+        ));
+        let depth = BACKGROUND_DEPTH + 0.01 * (index + 1) as f32;
+
+        for _ in 0..layer.count {
+            // Twinkling pieces fade on their own, so each needs a material of its own
+            let material = if layer.twinkle {
+                materials.add(ColorMaterial::modulated_texture(
+                    texture.clone(),
+                    layer.color(),
+                ))
+            } else {
+                shared.clone()
+            };
+            let x = rng.gen_range(-arena.width / 2.0..arena.width / 2.0);
+            let y = rng.gen_range(-arena.height / 2.0..arena.height / 2.0);
+
+            commands
+                .spawn_bundle(SpriteBundle {
+                    material,
+                    transform: Transform::from_xyz(x, y, depth),
+                    sprite: Sprite::new(Vec2::new(layer.size[0], layer.size[1])),
+                    ..Default::default()
+                })
+                .insert(ParallaxPiece {
+                    layer: index,
+                    phase: rng.gen_range(0.0..std::f32::consts::TAU),
+                });
+        }
+    }
+}
+
+/// Wraps `value` around to the other side of `-extent / 2.0..extent / 2.0`, once it is all
+/// the way past one of them.
+fn wrap(value: f32, extent: f32, size: f32) -> f32 {
+    let edge = (extent + size) / 2.0;
+    if value < -edge {
+        value + 2.0 * edge
+    } else if value > edge {
+        value - 2.0 * edge
+    } else {
+        value
+    }
+}
+
+fn scroll_layers(
+    game_state: Res<GameState>,
+    speed: Res<Speed>,
+    theme: Res<Theme>,
+    arena: Res<Arena>,
+    settings: Res<Settings>,
+    mut pieces: Query<(&ParallaxPiece, &mut Transform, &mut Visible)>,
+) {
+    let shown = settings.effects.background;
+    let running = !game_state.is_not_running();
+
+    for (piece, mut transform, mut visible) in pieces.iter_mut() {
+        if visible.is_visible != shown {
+            visible.is_visible = shown;
+        }
+
+        let layer = match theme.layers.get(piece.layer) {
+            Some(layer) if running => layer,
+            _ => continue,
+        };
+        let position = &mut transform.translation;
+        position.y = wrap(
+            position.y - speed.value() * layer.speed,
+            arena.height,
+            layer.size[1],
+        );
+        position.x = wrap(position.x + layer.drift, arena.width, layer.size[0]);
+    }
+}
+
+fn twinkle(
+    time: Res<Time>,
+    theme: Res<Theme>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    pieces: Query<(&ParallaxPiece, &Handle<ColorMaterial>)>,
+) {
+    let seconds = time.seconds_since_startup() as f32;
+    for (piece, handle) in pieces.iter() {
+        let layer = match theme.layers.get(piece.layer) {
+            Some(layer) if layer.twinkle => layer,
+            _ => continue,
+        };
+
+        let glow = 0.6 + 0.4 * (seconds * TWINKLE_SPEED + piece.phase).sin();
+        if let Some(material) = materials.get_mut(handle) {
+            material.color.set_a(layer.alpha.clamp(0.0, 1.0) * glow);
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use bevy::prelude::*;
+
+use super::{
+    achievements::AchievementsScreen, any_screen_open, arena::HudAnchor, mode::CurrentMode,
+    stats::StatsScreen, ArenaViewport, FocusLost, GameState, GameStoppedByHandler, PauseRequested,
+    StartRequested,
+};
+use crate::{fonts::Fonts, localization::Localization, settings::SettingsScreen};
+
+/// Dims the game while it is paused and offers to resume or end it. Games also get paused
+/// when the window loses focus.
+pub(super) struct PausePlugin;
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(PauseMenu::default());
+        app.add_system(pause_on_focus_loss.system());
+        app.add_system(navigate_pause_menu.system());
+        app.add_system(render_pause_menu.system());
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Resume,
+    EndGame,
+}
+
+static ACTIONS: [Action; 2] = [Action::Resume, Action::EndGame];
+
+impl Action {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Resume => "pause-resume",
+            Self::EndGame => "pause-end-game",
+        }
+    }
+}
+
+#[derive(Default)]
+struct PauseMenu {
+    selected: usize,
+}
+
+/// Paused in the middle of a game, rather than waiting for one to start.
+fn is_paused(game_state: GameState, game_stopped: &GameStoppedByHandler) -> bool {
+    game_state.is_stopped() && *game_stopped == GameStoppedByHandler::No
+}
+
+fn pause_on_focus_loss(
+    mut focus_lost: EventReader<FocusLost>,
+    game_state: Res<GameState>,
+    mut pause_requests: EventWriter<PauseRequested>,
+) {
+    let lost_focus = focus_lost.iter().next().is_some();
+    if lost_focus && !game_state.is_stopped() {
+        pause_requests.send(PauseRequested);
+    }
+}
+
+fn navigate_pause_menu(
+    keyboard_input: Res<Input<KeyCode>>,
+    game_state: Res<GameState>,
+    game_stopped: Res<GameStoppedByHandler>,
+    settings_screen: Res<SettingsScreen>,
+    achievements_screen: Res<AchievementsScreen>,
+    stats_screen: Res<StatsScreen>,
+    mut menu: ResMut<PauseMenu>,
+    mut current_mode: ResMut<CurrentMode>,
+    mut start_requests: EventWriter<StartRequested>,
+) {
+    if !is_paused(*game_state, &game_stopped) {
+        // Every pause starts out on resuming
+        if menu.selected != 0 {
+            menu.selected = 0;
+        }
+        return;
+    }
+    if any_screen_open(&settings_screen, &achievements_screen, &stats_screen) {
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Up) {
+        menu.selected = (menu.selected + ACTIONS.len() - 1) % ACTIONS.len();
+    } else if keyboard_input.just_pressed(KeyCode::Down) {
+        menu.selected = (menu.selected + 1) % ACTIONS.len();
+    } else if keyboard_input.just_pressed(KeyCode::Return) {
+        match ACTIONS[menu.selected] {
+            Action::Resume => start_requests.send(StartRequested),
+            // Ends up as a regular game over, scored as it stands
+            Action::EndGame => current_mode.finish(),
+        }
+    }
+}
+
+struct PauseOverlay;
+struct PauseText;
+
+fn render_pause_menu(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    game_state: Res<GameState>,
+    game_stopped: Res<GameStoppedByHandler>,
+    menu: Res<PauseMenu>,
+    settings_screen: Res<SettingsScreen>,
+    achievements_screen: Res<AchievementsScreen>,
+    stats_screen: Res<StatsScreen>,
+    localization: Res<Localization>,
+    viewport: Res<ArenaViewport>,
+    fonts: Res<Fonts>,
+    overlays: Query<Entity, With<PauseOverlay>>,
+    mut texts: Query<&mut Text, With<PauseText>>,
+) {
+    let shown = is_paused(*game_state, &game_stopped)
+        && !any_screen_open(&settings_screen, &achievements_screen, &stats_screen);
+    if !shown {
+        for overlay in overlays.iter() {
+            commands.entity(overlay).despawn_recursive();
+        }
+        return;
+    }
+
+    let needs_redraw = menu.is_changed()
+        || localization.is_changed()
+        || viewport.is_changed()
+        || overlays.iter().next().is_none();
+    if !needs_redraw {
+        return;
+    }
+
+    let font_size = 22.0 * viewport.pixels_per_unit;
+    let section = |value: String, color: Color| TextSection {
+        value,
+        style: TextStyle {
+            font: fonts.ui.clone(),
+            font_size,
+            color,
+        },
+    };
+
+    let mut sections = vec![section(
+        localization.get("pause-title") + "\n\n",
+        Color::rgb(0.0, 0.0, 0.0),
+    )];
+    for (index, action) in ACTIONS.iter().enumerate() {
+        let color = if index == menu.selected {
+            Color::rgb_u8(46, 110, 180)
+        } else {
+            Color::rgb(0.0, 0.0, 0.0)
+        };
+        sections.push(section(localization.get(action.label()) + "\n", color));
+    }
+    sections.push(section(
+        format!("\n{}", localization.get("pause-hint")),
+        Color::rgb(0.3, 0.3, 0.3),
+    ));
+
+    if let Some(mut text) = texts.iter_mut().next() {
+        text.sections = sections;
+        return;
+    }
+
+    // The whole window gets dimmed, with the menu on top
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(0.0, 0.0, 0.0, 0.45).into()),
+            ..Default::default()
+        })
+        .insert(PauseOverlay)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        padding: Rect::all(Val::Px(10.0)),
+                        ..Default::default()
+                    },
+                    material: materials.add(Color::rgba(1.0, 1.0, 1.0, 0.92).into()),
+                    ..Default::default()
+                })
+                .insert(HudAnchor::top(120.0, 200.0).with_size(Vec2::new(300.0, 180.0)))
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(TextBundle {
+                            text: Text {
+                                sections,
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        })
+                        .insert(PauseText);
+                });
+        });
+}
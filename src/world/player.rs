@@ -1,10 +1,15 @@
 use bevy::{prelude::*, sprite::collide_aabb::collide};
 
 use super::{
-    falling_object::{ObjectKind, Speed},
-    scoreboard::{Lives, Scoreboard},
-    GameState, Wall,
+    arena::{Arena, Obstacle, Wall},
+    falling_object::{Effect, ObjectKind, ObjectRegistry, Speed},
+    mode::CurrentMode,
+    replay::GameClock,
+    scoreboard::{Combo, Lives, Misses, Scoreboard},
+    theme::{Theme, Themed},
+    EffectRequested, GameState, ObjectCaught, PlayerDied,
 };
+use crate::settings::Settings;
 
 pub static PLAYER_STAR_COLLISION_SYSTEM_LABEL: &str = "player_star_collision_system";
 static PLAYER_WALL_COLLISION_LABEL: &str = "player_wall_collision_system";
@@ -13,7 +18,8 @@ pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_startup_system(spawn_player.system());
+        app.insert_resource(Wind(0.0));
+        app.add_startup_system(spawn_players.system());
         app.add_system(keyboard_input.system().label(KEYBORD_INPUT_LABEL));
         app.add_system(
             handle_player_falling_object_collision
@@ -27,45 +33,145 @@ impl Plugin for PlayerPlugin {
                 .after(KEYBORD_INPUT_LABEL),
         );
         app.add_system(apply_delta.system().after(PLAYER_WALL_COLLISION_LABEL));
+        app.add_system(hide_dead_players.system());
+        app.add_system(blink_invulnerable_players.system());
     }
 }
 
-pub struct Player;
+pub const MAX_PLAYERS: usize = 2;
+
+/// A cloud, along with its own [`Scoreboard`] and [`Lives`]. Players are numbered from 0.
+pub struct Player(pub usize);
+
+impl Player {
+    /// The color the cloud, and the boards, of the player are tinted with.
+    pub fn color(&self) -> Color {
+        match self.0 {
+            0 => Color::WHITE,
+            _ => Color::rgb_u8(255, 200, 150),
+        }
+    }
+}
 
 struct PositionDelta(f32);
 
+/// Seconds a cloud blinks for after losing a life, when it can't lose another one.
+pub const GRACE_PERIOD: f32 = 2.0;
+/// Seconds the cloud stays shown, or hidden, while blinking.
+const BLINK_INTERVAL: f32 = 0.15;
+
+/// Keeps a cloud from losing lives for a moment, see [`GRACE_PERIOD`]. Every cloud has one,
+/// updated in place so that a life lost earlier in the frame already counts.
+#[derive(Default)]
+pub struct Invulnerable {
+    pub time_left: f32,
+}
+
+impl Invulnerable {
+    pub fn is_active(&self) -> bool {
+        self.time_left > 0.0
+    }
+}
+
+/// How far the clouds get pushed sideways every frame, like during a storm.
+pub struct Wind(pub f32);
+
+/// How many players the settings ask for, within what the game supports.
+pub fn player_count(settings: &Settings) -> usize {
+    settings.players.clamp(1, MAX_PLAYERS)
+}
+
 pub fn spawn_player(
-    mut commands: Commands,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    asset_server: Res<AssetServer>,
+    commands: &mut Commands,
+    materials: &mut Assets<ColorMaterial>,
+    cloud: Handle<Texture>,
+    arena: &Arena,
+    player: Player,
+    count: usize,
+    max_lives: u64,
 ) {
+    let spawn = arena.player_spawn(player.0, count);
+    let material = ColorMaterial::modulated_texture(cloud, player.color());
     commands
         .spawn_bundle(SpriteBundle {
-            material: materials.add(asset_server.load("cloud.png").into()),
-            transform: Transform::from_xyz(10.0, -200.0, 10.0),
+            material: materials.add(material),
+            transform: Transform::from_xyz(spawn.x, spawn.y, 10.0),
             sprite: Sprite::new(Vec2::new(115.5, 57.0)),
             ..Default::default()
         })
-        .insert(Player)
+        .insert(player)
+        .insert(Themed::Cloud)
+        .insert(Scoreboard::new())
+        .insert(Lives::new(max_lives))
+        .insert(Misses(0))
+        .insert(Combo::default())
+        .insert(Invulnerable::default())
         .insert(PositionDelta(0.0));
 }
 
+fn spawn_players(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+    arena: Res<Arena>,
+    theme: Res<Theme>,
+    settings: Res<Settings>,
+) {
+    let count = player_count(&settings);
+    for number in 0..count {
+        spawn_player(
+            &mut commands,
+            &mut materials,
+            asset_server.load(theme.cloud.as_str()),
+            &arena,
+            Player(number),
+            count,
+            settings.max_lives,
+        );
+    }
+}
+
 fn keyboard_input(
     keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<Settings>,
     game_running: Res<GameState>,
-    mut query: Query<&mut PositionDelta, With<Player>>,
+    wind: Res<Wind>,
+    mut query: Query<(&Player, &Lives, &mut PositionDelta)>,
 ) {
-    if game_running.is_not_running() {
+    // The clouds can already move during the countdown
+    if game_running.is_stopped() {
         return;
     }
 
+    // On their own, a player can use both sets of keys. Otherwise the arrows belong to the
+    // second player.
+    let controls = settings.controls;
+    let alone = query.iter_mut().count() == 1;
+    let keys = |player: &Player| match player.0 {
+        _ if alone => vec![
+            (controls.left.0, controls.right.0),
+            (KeyCode::Left, KeyCode::Right),
+        ],
+        0 => vec![(controls.left.0, controls.right.0)],
+        _ => vec![(KeyCode::Left, KeyCode::Right)],
+    };
+
     const STEP: f32 = 7.0;
-    for mut position_delta in query.iter_mut() {
-        if keyboard_input.pressed(KeyCode::A) || keyboard_input.pressed(KeyCode::Left) {
-            position_delta.0 -= STEP;
+    for (player, lives, mut position_delta) in query.iter_mut() {
+        if lives.is_dead() {
+            continue;
         }
-        if keyboard_input.pressed(KeyCode::D) || keyboard_input.pressed(KeyCode::Right) {
-            position_delta.0 += STEP;
+
+        if !game_running.is_not_running() {
+            position_delta.0 += wind.0;
+        }
+        for (left, right) in keys(player) {
+            if keyboard_input.pressed(left) {
+                position_delta.0 -= STEP;
+            }
+            if keyboard_input.pressed(right) {
+                position_delta.0 += STEP;
+            }
         }
     }
 }
@@ -77,16 +183,114 @@ fn apply_delta(mut query: Query<(&mut Transform, &mut PositionDelta), With<Playe
     }
 }
 
+/// Players who run out of lives before the others leave the arena.
+fn hide_dead_players(
+    mut player_died: EventReader<PlayerDied>,
+    mut players: Query<(&Player, &mut Visible)>,
+) {
+    for died in player_died.iter() {
+        for (player, mut visible) in players.iter_mut() {
+            if player.0 == died.player {
+                visible.is_visible = false;
+            }
+        }
+    }
+}
+
+/// Takes a life from a player who isn't [`Invulnerable`], making them so for a moment if
+/// `grace_period` is on and reporting their death if it was their last.
+pub fn take_life(
+    player: &Player,
+    lives: &mut Lives,
+    invulnerable: &mut Invulnerable,
+    grace_period: bool,
+    player_died: &mut EventWriter<PlayerDied>,
+) {
+    if invulnerable.is_active() {
+        return;
+    }
+
+    if lives.remove_life() {
+        player_died.send(PlayerDied { player: player.0 });
+    } else if grace_period {
+        invulnerable.time_left = GRACE_PERIOD;
+    }
+}
+
+fn blink_invulnerable_players(
+    clock: Res<GameClock>,
+    game_running: Res<GameState>,
+    mut players: Query<(&Lives, &mut Invulnerable, &mut Visible), With<Player>>,
+) {
+    for (lives, mut invulnerable, mut visible) in players.iter_mut() {
+        if !invulnerable.is_active() {
+            continue;
+        }
+
+        // A cloud paused in the middle of a blink shouldn't stay hidden
+        if game_running.is_not_running() {
+            visible.is_visible = !lives.is_dead();
+            continue;
+        }
+
+        invulnerable.time_left -= clock.delta_seconds();
+        if !invulnerable.is_active() || lives.is_dead() {
+            invulnerable.time_left = 0.0;
+            visible.is_visible = !lives.is_dead();
+            continue;
+        }
+
+        let blink = (invulnerable.time_left / BLINK_INTERVAL) as u32;
+        visible.is_visible = blink % 2 == 0;
+    }
+}
+
 fn handle_player_falling_object_collision(
     mut commands: Commands,
-    mut scoreboard: ResMut<Scoreboard>,
-    mut lives: ResMut<Lives>,
+    game_running: Res<GameState>,
     mut speed: ResMut<Speed>,
-    players: Query<(&Transform, &Sprite), With<Player>>,
+    current_mode: Res<CurrentMode>,
+    settings: Res<Settings>,
+    registry: Res<ObjectRegistry>,
+    mut object_caught: EventWriter<ObjectCaught>,
+    mut player_died: EventWriter<PlayerDied>,
+    mut effect_requests: EventWriter<EffectRequested>,
+    mut players: Query<(
+        &Player,
+        &Transform,
+        &Sprite,
+        &mut Scoreboard,
+        &mut Lives,
+        &mut Invulnerable,
+        &mut Combo,
+    )>,
     falling_objects: Query<(Entity, &Transform, &Sprite, &ObjectKind)>,
 ) {
-    for (player_transform, player_sprite) in players.iter() {
+    if game_running.is_not_running() {
+        return;
+    }
+
+    let mut caught = Vec::new();
+    for (
+        player,
+        player_transform,
+        player_sprite,
+        mut scoreboard,
+        mut lives,
+        mut invulnerable,
+        mut combo,
+    ) in players.iter_mut()
+    {
+        if lives.is_dead() {
+            continue;
+        }
+
         for (entity, transform, sprite, kind) in falling_objects.iter() {
+            // Both clouds can touch the same object, only the first one catches it
+            if caught.contains(&entity) {
+                continue;
+            }
+
             let collision = collide(
                 player_transform.translation,
                 player_sprite.size,
@@ -95,27 +299,55 @@ fn handle_player_falling_object_collision(
             );
 
             if collision.is_some() {
-                match kind {
-                    ObjectKind::Star => {
-                        scoreboard.add_point();
-                        if speed.should_increase(*scoreboard) {
-                            speed.increase();
+                for effect in registry.get(*kind).on_catch.iter() {
+                    match *effect {
+                        Effect::AddPoints { points } if points > 0 => {
+                            let before = scoreboard.score();
+                            scoreboard.add_points(points);
+                            if current_mode.has_automatic_speed() {
+                                speed.increase_between(before, scoreboard.score());
+                            }
+                            combo.add_catch();
+                        }
+                        Effect::AddPoints { points } => scoreboard.add_points(points),
+                        Effect::AddLife if !lives.is_full() => lives.add_life(),
+                        Effect::AddLife => (),
+                        Effect::RemoveLife => {
+                            if current_mode.has_lives() {
+                                take_life(
+                                    player,
+                                    &mut lives,
+                                    &mut invulnerable,
+                                    settings.grace_period,
+                                    &mut player_died,
+                                );
+                            }
+                        }
+                        Effect::ApplyEffect { effect } => {
+                            effect_requests.send(EffectRequested(effect))
                         }
                     }
-                    ObjectKind::Heart => {
-                        lives.add_life();
-                    }
-                };
+                }
+                object_caught.send(ObjectCaught {
+                    player: player.0,
+                    kind: *kind,
+                });
                 commands.entity(entity).despawn();
+                caught.push(entity);
             }
         }
     }
 }
 
 fn handle_player_wall_collision(
+    game_running: Res<GameState>,
     mut players: Query<(&mut PositionDelta, &Transform, &Sprite), With<Player>>,
-    walls: Query<(&Transform, &Sprite), With<Wall>>,
+    walls: Query<(&Transform, &Sprite), Or<(With<Wall>, With<Obstacle>)>>,
 ) {
+    if game_running.is_stopped() {
+        return;
+    }
+
     for (mut delta, transform, sprite) in players.iter_mut() {
         for (wall_transform, wall_sprite) in walls.iter() {
             let collision = collide(
@@ -126,11 +358,13 @@ fn handle_player_wall_collision(
             );
 
             if let Some(collision) = collision {
-                use bevy::sprite::collide_aabb::Collision::{Left, Right};
+                use bevy::sprite::collide_aabb::Collision::{Bottom, Left, Right, Top};
                 delta.0 = match collision {
                     Left => -1.0,
                     Right => 1.0,
-                    _ => unreachable!(),
+                    // The cloud only moves sideways, so an obstacle touching it from above or
+                    // below shouldn't stop it
+                    Top | Bottom => continue,
                 }
             }
         }
@@ -0,0 +1,457 @@
+use bevy::{input::InputSystem, prelude::*, window::WindowFocused};
+use std::{fmt::Write, fs, path::Path};
+
+use super::{falling_object::SpawnRng, FocusLost, GameOver};
+use crate::settings::{Difficulty, GameMode, Key, Settings, SettingsOverrides};
+
+static PLAY_BACK_LABEL: &str = "replay_play_back_system";
+
+/// How long every frame lasts while a replay plays back, headless runs included.
+const FRAME_SECONDS: f32 = 1.0 / 60.0;
+
+/// Records the keys pressed, and the times the window lost focus, during every game, and plays
+/// them back when a [`Replay`] has been
+/// inserted before the plugin is added.
+///
+/// The last recording is saved to `last-replay.txt` in the user's data directory once the
+/// player dies.
+pub(super) struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let world = app.world();
+        let seed = world.get_resource::<SpawnRng>().unwrap().seed();
+        let settings = world.get_resource::<Settings>().unwrap();
+        let recording = Replay {
+            mode: Some(settings.mode),
+            difficulty: Some(settings.difficulty),
+            players: Some(settings.players),
+            grace_period: Some(settings.grace_period),
+            max_lives: Some(settings.max_lives),
+            arena: Some(settings.arena.clone()),
+            ..Replay::new(seed)
+        };
+        let clock = GameClock {
+            fixed: world.get_resource::<Replay>().is_some(),
+            delta: FRAME_SECONDS,
+        };
+        app.insert_resource(Recording(recording));
+        app.insert_resource(clock);
+        app.insert_resource(Frame(0));
+
+        app.add_system_to_stage(CoreStage::PreUpdate, tick_clock.system());
+        app.add_system_to_stage(
+            CoreStage::PreUpdate,
+            play_back.system().label(PLAY_BACK_LABEL).after(InputSystem),
+        );
+        app.add_system_to_stage(
+            CoreStage::PreUpdate,
+            detect_focus_loss.system().label(PLAY_BACK_LABEL),
+        );
+        app.add_system_to_stage(CoreStage::PreUpdate, record.system().after(PLAY_BACK_LABEL));
+        app.add_system(save_recording.system());
+        app.add_system_to_stage(CoreStage::Last, count_frames.system());
+    }
+}
+
+/// The keys pressed and released during a run, and the seed and settings it was played with.
+///
+/// Stored as text: a `seed N` line, then optionally `mode NAME`, `difficulty NAME`,
+/// `players N`, `grace-period BOOL`, `max-lives N`, `arena NAME` and `daily-seed N` lines,
+/// followed by `FRAME press KEY`, `FRAME release KEY` and `FRAME focus-lost` lines.
+pub struct Replay {
+    pub seed: u64,
+    pub mode: Option<GameMode>,
+    pub difficulty: Option<Difficulty>,
+    pub players: Option<usize>,
+    pub grace_period: Option<bool>,
+    pub max_lives: Option<u64>,
+    pub arena: Option<String>,
+    /// The seed daily games were played with, which depends on the day they were played on
+    pub daily_seed: Option<u64>,
+    events: Vec<ReplayEvent>,
+}
+
+#[derive(PartialEq, Debug)]
+struct ReplayEvent {
+    frame: u64,
+    input: ReplayInput,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum ReplayInput {
+    Press(Key),
+    Release(Key),
+    /// The window lost focus, which pauses the game
+    FocusLost,
+}
+
+impl Replay {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            mode: None,
+            difficulty: None,
+            players: None,
+            grace_period: None,
+            max_lives: None,
+            arena: None,
+            daily_seed: None,
+            events: Vec::new(),
+        }
+    }
+
+    /// Plays the replay with the settings it was recorded with, without saving them.
+    pub fn apply(&self, overrides: &mut SettingsOverrides) {
+        overrides.mode = self.mode.or(overrides.mode);
+        overrides.difficulty = self.difficulty.or(overrides.difficulty);
+        overrides.players = self.players.or(overrides.players);
+        overrides.grace_period = self.grace_period.or(overrides.grace_period);
+        overrides.max_lives = self.max_lives.or(overrides.max_lives);
+        if let Some(arena) = &self.arena {
+            overrides.arena = Some(arena.clone());
+        }
+    }
+
+    /// A replay which only starts a game, for running without anyone at the keyboard.
+    pub fn autostart(seed: u64, start: Key) -> Self {
+        let mut replay = Self::new(seed);
+        replay.events.push(ReplayEvent {
+            frame: 0,
+            input: ReplayInput::Press(start),
+        });
+        replay.events.push(ReplayEvent {
+            frame: 1,
+            input: ReplayInput::Release(start),
+        });
+        replay
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut lines = contents.lines().filter(|line| !line.trim().is_empty());
+
+        let seed = lines
+            .next()
+            .and_then(|line| line.strip_prefix("seed "))
+            .and_then(|seed| seed.trim().parse().ok())
+            .ok_or_else(|| "the first line should be `seed N`".to_string())?;
+
+        let mut replay = Self::new(seed);
+        for line in lines {
+            let invalid = || format!("invalid line `{}`", line);
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            match parts.as_slice() {
+                ["mode", name] => {
+                    replay.mode = Some(GameMode::from_name(name).ok_or_else(invalid)?)
+                }
+                ["difficulty", name] => {
+                    replay.difficulty = Some(Difficulty::from_name(name).ok_or_else(invalid)?)
+                }
+                ["players", count] => replay.players = Some(count.parse().map_err(|_| invalid())?),
+                ["grace-period", value] => {
+                    replay.grace_period = Some(value.parse().map_err(|_| invalid())?)
+                }
+                ["max-lives", count] => {
+                    replay.max_lives = Some(count.parse().map_err(|_| invalid())?)
+                }
+                ["arena", name] => replay.arena = Some(name.to_string()),
+                ["daily-seed", seed] => {
+                    replay.daily_seed = Some(seed.parse().map_err(|_| invalid())?)
+                }
+                [frame, "focus-lost"] => replay.events.push(ReplayEvent {
+                    frame: frame.parse().map_err(|_| invalid())?,
+                    input: ReplayInput::FocusLost,
+                }),
+                [frame, action, key] => {
+                    let event = frame.parse().ok().and_then(|frame| {
+                        let key = Key::from_name(key)?;
+                        Some(ReplayEvent {
+                            frame,
+                            input: match *action {
+                                "press" => ReplayInput::Press(key),
+                                "release" => ReplayInput::Release(key),
+                                _ => return None,
+                            },
+                        })
+                    });
+                    replay.events.push(event.ok_or_else(invalid)?);
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(replay)
+    }
+
+    fn save(&self, path: &Path) -> Result<(), String> {
+        let mut contents = format!("seed {}\n", self.seed);
+        if let Some(mode) = self.mode {
+            let _ = writeln!(contents, "mode {}", mode.name());
+        }
+        if let Some(difficulty) = self.difficulty {
+            let _ = writeln!(contents, "difficulty {}", difficulty.name());
+        }
+        if let Some(players) = self.players {
+            let _ = writeln!(contents, "players {}", players);
+        }
+        if let Some(grace_period) = self.grace_period {
+            let _ = writeln!(contents, "grace-period {}", grace_period);
+        }
+        if let Some(max_lives) = self.max_lives {
+            let _ = writeln!(contents, "max-lives {}", max_lives);
+        }
+        if let Some(arena) = &self.arena {
+            let _ = writeln!(contents, "arena {}", arena);
+        }
+        if let Some(daily_seed) = self.daily_seed {
+            let _ = writeln!(contents, "daily-seed {}", daily_seed);
+        }
+        for event in self.events.iter() {
+            let _ = match event.input {
+                ReplayInput::Press(key) => {
+                    writeln!(contents, "{} press {}", event.frame, key.name())
+                }
+                ReplayInput::Release(key) => {
+                    writeln!(contents, "{} release {}", event.frame, key.name())
+                }
+                ReplayInput::FocusLost => writeln!(contents, "{} focus-lost", event.frame),
+            };
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        fs::write(path, contents).map_err(|e| e.to_string())
+    }
+}
+
+pub struct Recording(Replay);
+
+impl Recording {
+    pub fn record_daily_seed(&mut self, seed: u64) {
+        self.0.daily_seed = Some(seed);
+    }
+}
+
+/// The time game logic goes by. While a replay plays back, which it does frame by frame,
+/// every frame lasts exactly as long, however long it really took.
+pub struct GameClock {
+    fixed: bool,
+    delta: f32,
+}
+
+impl GameClock {
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta
+    }
+}
+
+fn tick_clock(time: Res<Time>, mut clock: ResMut<GameClock>) {
+    if !clock.fixed {
+        clock.delta = time.delta_seconds();
+    }
+}
+
+/// How many frames have passed since the game was launched.
+struct Frame(u64);
+
+fn play_back(
+    frame: Res<Frame>,
+    replay: Option<Res<Replay>>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut focus_lost: EventWriter<FocusLost>,
+) {
+    let replay = match replay {
+        Some(replay) => replay,
+        None => return,
+    };
+
+    for event in replay.events.iter().filter(|event| event.frame == frame.0) {
+        match event.input {
+            ReplayInput::Press(key) => keyboard_input.press(key.0),
+            ReplayInput::Release(key) => keyboard_input.release(key.0),
+            ReplayInput::FocusLost => focus_lost.send(FocusLost),
+        }
+    }
+}
+
+/// While a replay plays back, only the focus it lost counts, not what happens to the window.
+fn detect_focus_loss(
+    replay: Option<Res<Replay>>,
+    mut window_focused: EventReader<WindowFocused>,
+    mut focus_lost: EventWriter<FocusLost>,
+) {
+    let lost = window_focused.iter().any(|event| !event.focused);
+    if lost && replay.is_none() {
+        focus_lost.send(FocusLost);
+    }
+}
+
+fn record(
+    frame: Res<Frame>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut focus_lost: EventReader<FocusLost>,
+    mut recording: ResMut<Recording>,
+) {
+    let pressed = keyboard_input.get_just_pressed().map(|key| (key, true));
+    let released = keyboard_input.get_just_released().map(|key| (key, false));
+
+    for (key_code, pressed) in pressed.chain(released) {
+        // Every key the game reads has a name, the others can't affect it
+        if let Some(key) = Key::from_key_code(*key_code) {
+            recording.0.events.push(ReplayEvent {
+                frame: frame.0,
+                input: if pressed {
+                    ReplayInput::Press(key)
+                } else {
+                    ReplayInput::Release(key)
+                },
+            });
+        }
+    }
+    if focus_lost.iter().next().is_some() {
+        recording.0.events.push(ReplayEvent {
+            frame: frame.0,
+            input: ReplayInput::FocusLost,
+        });
+    }
+}
+
+fn save_recording(mut game_over: EventReader<GameOver>, recording: Res<Recording>) {
+    if game_over.iter().next().is_none() {
+        return;
+    }
+
+    let path = match dirs::data_dir() {
+        Some(dir) => dir.join("starhigh").join("last-replay.txt"),
+        None => return,
+    };
+    if let Err(error) = recording.0.save(&path) {
+        warn!("Could not save the replay to {}: {}", path.display(), error);
+    }
+}
+
+fn count_frames(mut frame: ResMut<Frame>) {
+    frame.0 += 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{
+        falling_object::Speed,
+        mode::{change_speed, CurrentMode},
+        GameState,
+    };
+    use bevy::{app::Events, window::WindowId};
+    use std::process;
+
+    /// What happens on each frame of the recorded game.
+    enum Step {
+        Tap(KeyCode),
+        LoseFocus,
+    }
+
+    /// Plays `frames` frames of a zen game, with `inputs` coming from the keyboard and the
+    /// window, returning the speed it ends up at and what got recorded.
+    fn play(replay: Option<Replay>, inputs: &[(u64, Step)], frames: u64) -> (f32, Replay) {
+        let mut world = World::default();
+        world.insert_resource(Frame(0));
+        world.insert_resource(Recording(Replay::new(7)));
+        world.insert_resource(Input::<KeyCode>::default());
+        world.insert_resource(Events::<WindowFocused>::default());
+        world.insert_resource(Events::<FocusLost>::default());
+        world.insert_resource(GameState::Running);
+        world.insert_resource(CurrentMode::new(GameMode::Zen));
+        world.insert_resource(Speed::new(Difficulty::Normal));
+        if let Some(replay) = replay {
+            world.insert_resource(replay);
+        }
+
+        let mut stage = SystemStage::single_threaded();
+        stage.add_system(play_back.system().label(PLAY_BACK_LABEL));
+        stage.add_system(detect_focus_loss.system().label(PLAY_BACK_LABEL));
+        stage.add_system(record.system().label("record").after(PLAY_BACK_LABEL));
+        stage.add_system(change_speed.system().after("record"));
+
+        for frame in 0..frames {
+            let mut keyboard_input = world.get_resource_mut::<Input<KeyCode>>().unwrap();
+            keyboard_input.update();
+            for (at, input) in inputs.iter() {
+                match input {
+                    Step::Tap(key) if *at == frame => keyboard_input.press(*key),
+                    Step::Tap(key) if *at + 1 == frame => keyboard_input.release(*key),
+                    _ => (),
+                }
+            }
+            let lose_focus = inputs
+                .iter()
+                .any(|(at, input)| *at == frame && matches!(input, Step::LoseFocus));
+            if lose_focus {
+                world
+                    .get_resource_mut::<Events<WindowFocused>>()
+                    .unwrap()
+                    .send(WindowFocused {
+                        id: WindowId::primary(),
+                        focused: false,
+                    });
+            }
+
+            stage.run(&mut world);
+            world.get_resource_mut::<Frame>().unwrap().0 += 1;
+        }
+
+        let speed = world.get_resource::<Speed>().unwrap().value();
+        (speed, world.remove_resource::<Recording>().unwrap().0)
+    }
+
+    #[test]
+    fn plays_back_the_speed_keys_of_a_zen_game() {
+        let inputs = [
+            (2, Step::Tap(KeyCode::Equals)),
+            (5, Step::Tap(KeyCode::NumpadAdd)),
+            (8, Step::Tap(KeyCode::Plus)),
+            (11, Step::Tap(KeyCode::Minus)),
+            (14, Step::LoseFocus),
+            (17, Step::Tap(KeyCode::NumpadAdd)),
+            (20, Step::Tap(KeyCode::NumpadSubtract)),
+            (23, Step::Tap(KeyCode::Equals)),
+        ];
+        let (speed, recording) = play(None, &inputs, 30);
+        assert!(speed > Speed::new(Difficulty::Normal).value());
+
+        // The replay goes through a file, like it would between two runs of the game
+        let path = std::env::temp_dir().join(format!("starhigh-replay-{}.txt", process::id()));
+        recording.save(&path).unwrap();
+        let replay = Replay::load(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(replay.events, recording.events);
+
+        let (replayed_speed, replayed) = play(Some(replay), &[], 30);
+        assert_eq!(replayed_speed, speed);
+        assert_eq!(replayed.events, recording.events);
+    }
+
+    #[test]
+    fn keeps_the_gameplay_settings() {
+        let mut replay = Replay::new(3);
+        replay.mode = Some(GameMode::Zen);
+        replay.grace_period = Some(false);
+        replay.max_lives = Some(2);
+        replay.arena = Some("platforms".to_string());
+
+        let path = std::env::temp_dir().join(format!("starhigh-settings-{}.txt", process::id()));
+        replay.save(&path).unwrap();
+        let loaded = Replay::load(&path).unwrap();
+        let _ = fs::remove_file(&path);
+
+        let mut overrides = SettingsOverrides::new(Settings::default());
+        loaded.apply(&mut overrides);
+        let settings = overrides.apply();
+        assert_eq!(settings.mode, GameMode::Zen);
+        assert!(!settings.grace_period);
+        assert_eq!(settings.max_lives, 2);
+        assert_eq!(settings.arena, "platforms");
+    }
+}
@@ -1,189 +1,27 @@
-use bevy::prelude::*;
+mod hud;
+
+use bevy::{prelude::*, utils::HashMap};
 use std::{env, fs, io::Read, io::Write};
 
-use super::player::PLAYER_STAR_COLLISION_SYSTEM_LABEL;
+use crate::settings::GameMode;
 
 pub struct ScoreboardPlugin;
 
 impl Plugin for ScoreboardPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.insert_resource(Scoreboard::new());
-        app.insert_resource(Lives::new());
-        app.insert_resource(HighScore::load_from_file());
-        app.add_startup_system(spawn_boards.system());
-        app.add_system(
-            update_boards
-                .system()
-                .after(PLAYER_STAR_COLLISION_SYSTEM_LABEL),
-        );
+        app.insert_resource(HighScores::load_from_file());
+        app.add_plugin(hud::HudPlugin);
     }
 }
 
-#[derive(PartialEq, Eq)]
-pub(super) enum BoardType {
-    Score,
-    Lives,
-    HighScore,
-}
-
-fn spawn_boards(
-    mut commands: Commands,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    asset_server: Res<AssetServer>,
-    highscore: Res<HighScore>,
-) {
-    commands.spawn_bundle(ImageBundle {
-        material: materials.add(asset_server.load("star.png").into()),
-        style: Style {
-            position_type: PositionType::Absolute,
-            position: Rect {
-                top: Val::Px(5.0),
-                left: Val::Px(5.0),
-                ..Default::default()
-            },
-            ..Default::default()
-        },
-        ..Default::default()
-    });
-    commands
-        .spawn_bundle(TextBundle {
-            text: Text {
-                sections: vec![TextSection {
-                    value: "".to_string(),
-                    style: TextStyle {
-                        font: asset_server.load("FiraCode-Regular.ttf"),
-                        font_size: 40.0,
-                        color: Color::rgb(0.0, 0.0, 0.0),
-                    },
-                }],
-                ..Default::default()
-            },
-            style: Style {
-                position_type: PositionType::Absolute,
-                position: Rect {
-                    top: Val::Px(5.0),
-                    left: Val::Px(50.0),
-                    ..Default::default()
-                },
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(BoardType::Score);
-
-    commands.spawn_bundle(ImageBundle {
-        material: materials.add(asset_server.load("heart.png").into()),
-        style: Style {
-            position_type: PositionType::Absolute,
-            position: Rect {
-                top: Val::Px(45.0),
-                left: Val::Px(5.0),
-                ..Default::default()
-            },
-            ..Default::default()
-        },
-        ..Default::default()
-    });
-
-    commands
-        .spawn_bundle(TextBundle {
-            text: Text {
-                sections: vec![TextSection {
-                    value: "".to_string(),
-                    style: TextStyle {
-                        font: asset_server.load("FiraCode-Regular.ttf"),
-                        font_size: 40.0,
-                        color: Color::rgb(0.0, 0.0, 0.0),
-                    },
-                }],
-                ..Default::default()
-            },
-            style: Style {
-                position_type: PositionType::Absolute,
-                position: Rect {
-                    top: Val::Px(45.0),
-                    left: Val::Px(50.0),
-                    ..Default::default()
-                },
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(BoardType::Lives);
-
-    commands.spawn_bundle(ImageBundle {
-        material: materials.add(asset_server.load("high-score.png").into()),
-        style: Style {
-            position_type: PositionType::Absolute,
-            position: Rect {
-                top: Val::Px(100.0),
-                left: Val::Px(5.0),
-                ..Default::default()
-            },
-            ..Default::default()
-        },
-        ..Default::default()
-    });
-
-    commands.spawn_bundle(ImageBundle {
-        material: materials.add(asset_server.load("star.png").into()),
-        style: Style {
-            position_type: PositionType::Absolute,
-            position: Rect {
-                top: Val::Px(145.0),
-                left: Val::Px(5.0),
-                ..Default::default()
-            },
-            ..Default::default()
-        },
-        ..Default::default()
-    });
-    commands
-        .spawn_bundle(TextBundle {
-            text: Text {
-                sections: vec![TextSection {
-                    value: highscore.value.to_string(),
-                    style: TextStyle {
-                        font: asset_server.load("FiraCode-Regular.ttf"),
-                        font_size: 40.0,
-                        color: Color::rgb(0.0, 0.0, 0.0),
-                    },
-                }],
-                ..Default::default()
-            },
-            style: Style {
-                position_type: PositionType::Absolute,
-                position: Rect {
-                    top: Val::Px(145.0),
-                    left: Val::Px(50.0),
-                    ..Default::default()
-                },
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(BoardType::HighScore);
-}
-
-fn update_boards(
-    scoreboard: Res<Scoreboard>,
-    lives: Res<Lives>,
-    mut query: Query<(&mut Text, &BoardType)>,
-) {
-    for (mut text, board_type) in query.iter_mut() {
-        match board_type {
-            BoardType::Score => text.sections[0].value = scoreboard.0.to_string(),
-            BoardType::Lives => text.sections[0].value = lives.0.to_string(),
-            BoardType::HighScore => (),
-        };
-    }
-}
-#[derive(Clone, Copy)]
-pub struct HighScore {
-    pub value: u64,
-}
+/// The best score reached in each mode.
+///
+/// Stored next to the executable as `highscore.dat`, one `MODE SCORE` line per mode. Files
+/// holding a single number, from before there were modes, count as the classic high score.
+#[derive(Clone, Default)]
+pub struct HighScores(HashMap<GameMode, u64>);
 
-impl HighScore {
+impl HighScores {
     fn get_file() -> Option<fs::File> {
         let current_exe = match env::current_exe() {
             Ok(exe) => exe,
@@ -216,28 +54,66 @@ impl HighScore {
     }
 
     pub fn load_from_file() -> Self {
-        if let Some(mut file) = Self::get_file() {
-            let mut string = String::new();
-            match file.read_to_string(&mut string) {
-                Ok(_) => match string.parse::<u64>() {
-                    Ok(value) => Self { value },
-                    _ => Self { value: 0 },
-                },
-                _ => Self { value: 0 },
+        let mut high_scores = Self::default();
+        let mut string = String::new();
+        match Self::get_file().map(|mut file| file.read_to_string(&mut string)) {
+            Some(Ok(_)) => (),
+            _ => return high_scores,
+        }
+
+        if let Ok(value) = string.trim().parse::<u64>() {
+            high_scores.0.insert(GameMode::Classic, value);
+            return high_scores;
+        }
+
+        for line in string.lines() {
+            let entry = line.split_once(' ').and_then(|(mode, value)| {
+                Some((
+                    GameMode::from_name(mode)?,
+                    value.trim().parse::<u64>().ok()?,
+                ))
+            });
+            if let Some((mode, value)) = entry {
+                high_scores.0.insert(mode, value);
             }
-        } else {
-            Self { value: 0 }
         }
+        high_scores
     }
 
     pub fn update_file(&self) {
         if let Some(mut file) = Self::get_file() {
-            match file.write(self.value.to_string().as_bytes()) {
+            // A shorter table would otherwise leave the end of the old one in the file
+            if file.set_len(0).is_err() {
+                return;
+            }
+            let mut contents = String::new();
+            for (mode, value) in self.0.iter() {
+                contents += &format!("{} {}\n", mode.name(), value);
+            }
+            match file.write(contents.as_bytes()) {
                 Ok(_) => (),
                 Err(_) => (),
             };
         }
     }
+
+    pub fn get(&self, mode: GameMode) -> u64 {
+        self.0.get(&mode).copied().unwrap_or(0)
+    }
+
+    /// The best score over every mode.
+    pub fn best(&self) -> u64 {
+        self.0.values().copied().max().unwrap_or(0)
+    }
+
+    /// Keeps `score` if it beats the high score of `mode`, returning whether it did.
+    pub fn record(&mut self, mode: GameMode, score: u64) -> bool {
+        if score <= self.get(mode) {
+            return false;
+        }
+        self.0.insert(mode, score);
+        true
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -248,14 +124,14 @@ impl Scoreboard {
         Self(0)
     }
 
-    pub fn add_point(&mut self) {
-        self.0 += 1;
-    }
-
-    pub fn remove_point(&mut self) {
-        if self.0 != 0 {
-            self.0 -= 1;
-        }
+    /// Adds, or with a negative amount removes, several points at once.
+    pub fn add_points(&mut self, points: i64) {
+        let removed = points.unsigned_abs();
+        self.0 = if points < 0 {
+            self.0.saturating_sub(removed)
+        } else {
+            self.0.saturating_add(removed)
+        };
     }
 
     pub fn score(&self) -> u64 {
@@ -263,22 +139,169 @@ impl Scoreboard {
     }
 }
 
-pub struct Lives(u64);
+/// Stars missed, shown instead of the lives in modes without them.
+pub struct Misses(pub u64);
+
+/// Stars caught in a row, without missing any.
+#[derive(Default)]
+pub struct Combo {
+    pub current: u64,
+    pub best: u64,
+}
+
+impl Combo {
+    pub fn add_catch(&mut self) {
+        self.current += 1;
+        self.best = self.best.max(self.current);
+    }
+
+    pub fn break_off(&mut self) {
+        self.current = 0;
+    }
+}
+
+/// Lives every game starts with, unless the maximum is lower.
+const STARTING_LIVES: u64 = 3;
+
+/// Lives which never go below zero, nor above the maximum from the settings. Changing them
+/// when they can't change is a no-op, check [`Lives::is_full`] or [`Lives::is_dead`] first to
+/// keep from marking the component as changed for nothing.
+pub struct Lives {
+    count: u64,
+    max: u64,
+}
 
 impl Lives {
-    pub fn new() -> Self {
-        Self(3)
+    pub fn new(max: u64) -> Self {
+        let max = max.max(1);
+        Self {
+            count: STARTING_LIVES.min(max),
+            max,
+        }
     }
 
     pub fn add_life(&mut self) {
-        self.0 += 1;
+        self.count = (self.count + 1).min(self.max);
+    }
+
+    /// Returns whether this was the last life, so that the death is only reported once.
+    pub fn remove_life(&mut self) -> bool {
+        if self.is_dead() {
+            return false;
+        }
+
+        self.count -= 1;
+        self.is_dead()
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn max(&self) -> u64 {
+        self.max
     }
 
-    pub fn remove_life(&mut self) {
-        self.0 -= 1;
+    pub fn is_full(&self) -> bool {
+        self.count >= self.max
     }
 
     pub fn is_dead(&self) -> bool {
-        self.0 == 0
+        self.count == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        embedded::AssetSource,
+        settings::Settings,
+        world::{
+            arena::Wall,
+            falling_object::{falling_object_wall_collision, Drift, ObjectKind, ObjectRegistry},
+            mode::CurrentMode,
+            player::{Invulnerable, Player},
+            EffectRequested, GameState, ObjectMissed, PlayerDied,
+        },
+    };
+    use bevy::app::Events;
+
+    #[test]
+    fn keeps_at_least_one_life() {
+        let lives = Lives::new(0);
+        assert_eq!(lives.max(), 1);
+        assert_eq!(lives.count(), 1);
+    }
+
+    #[test]
+    fn stops_adding_lives_at_the_maximum() {
+        let mut lives = Lives::new(4);
+        for _ in 0..10 {
+            lives.add_life();
+        }
+        assert_eq!(lives.count(), 4);
+        assert!(lives.is_full());
+    }
+
+    #[test]
+    fn stays_dead() {
+        let mut lives = Lives::new(1);
+        assert!(lives.remove_life());
+        assert!(!lives.remove_life());
+        assert_eq!(lives.count(), 0);
+    }
+
+    #[test]
+    fn reports_the_last_life_once() {
+        let mut lives = Lives::new(5);
+        let deaths = (0..10).filter(|_| lives.remove_life()).count();
+        assert_eq!(deaths, 1);
+        assert!(lives.is_dead());
+    }
+
+    #[test]
+    fn dies_once_to_misses_in_the_same_frame() {
+        let mut world = World::default();
+        world.insert_resource(GameState::Running);
+        world.insert_resource(CurrentMode::new(GameMode::Classic));
+        world.insert_resource(Settings::default());
+        world.insert_resource(ObjectRegistry::load(&AssetSource::new(None)));
+        world.insert_resource(Events::<ObjectMissed>::default());
+        world.insert_resource(Events::<PlayerDied>::default());
+        world.insert_resource(Events::<EffectRequested>::default());
+
+        world
+            .spawn()
+            .insert(Wall)
+            .insert(Transform::from_xyz(0.0, -300.0, 0.0))
+            .insert(Sprite::new(Vec2::new(1000.0, 10.0)));
+        // Both stars reach into the bottom wall at once
+        for x in [-100.0, 100.0].iter() {
+            world
+                .spawn()
+                .insert(ObjectKind::STAR)
+                .insert(Drift(0.0))
+                .insert(Transform::from_xyz(*x, -280.0, 0.0))
+                .insert(Sprite::new(Vec2::new(40.0, 40.0)));
+        }
+        let player = world
+            .spawn()
+            .insert(Player(0))
+            .insert(Transform::from_xyz(0.0, -200.0, 0.0))
+            .insert(Scoreboard::new())
+            .insert(Lives::new(1))
+            .insert(Invulnerable::default())
+            .insert(Misses(0))
+            .insert(Combo::default())
+            .id();
+
+        let mut stage = SystemStage::parallel();
+        stage.add_system(falling_object_wall_collision.system());
+        stage.run(&mut world);
+
+        let events = world.get_resource::<Events<PlayerDied>>().unwrap();
+        assert_eq!(events.get_reader().iter(events).count(), 1);
+        assert_eq!(world.get::<Lives>(player).unwrap().count(), 0);
     }
 }
@@ -0,0 +1,319 @@
+use bevy::prelude::*;
+
+use super::{HighScores, Lives, Misses, Scoreboard};
+use crate::{
+    fonts::Fonts,
+    world::{
+        mode::CurrentMode,
+        player::{Player, PLAYER_STAR_COLLISION_SYSTEM_LABEL},
+        theme::{Theme, Themed},
+        HudAnchor,
+    },
+};
+
+/// How many hearts fit in the row. Past that, lives are shown as a single heart and a count.
+const HEART_ROW: u64 = 5;
+const HEART_SIZE: f32 = 28.0;
+const HEART_SPACING: f32 = 32.0;
+/// How faint the hearts of lives which could still be gained are.
+const EMPTY_HEART_ALPHA: f32 = 0.25;
+/// Seconds a gained heart takes to shrink back into place, or a lost one to fade out.
+const HEART_ANIMATION: f32 = 0.4;
+/// How much of the gap to the real score the shown one closes every second.
+const ROLL_SPEED: f32 = 12.0;
+
+/// The scores, lives and timers drawn over the arena.
+pub(super) struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(spawn_boards.system());
+        app.add_system(
+            roll_scores
+                .system()
+                .after(PLAYER_STAR_COLLISION_SYSTEM_LABEL),
+        );
+        app.add_system(update_hearts.system());
+        app.add_system(update_lives_count.system());
+        app.add_system(update_boards.system());
+        app.add_system(show_player_boards.system());
+    }
+}
+
+/// The boards shared by every player.
+enum BoardType {
+    HighScore,
+    /// The time left in timed modes
+    Countdown,
+}
+
+/// Marks the boards, and their icons, showing the state of one player.
+struct BoardOwner(usize);
+
+/// A score which counts up, or down, to the real one instead of jumping to it.
+#[derive(Default)]
+struct RollingNumber {
+    shown: f32,
+}
+
+/// One heart in a player's row. Filled hearts stand for the lives left, faint ones for those
+/// which can still be gained.
+struct Heart {
+    index: u64,
+    board_top: f32,
+    filled: bool,
+    slot: bool,
+    /// Seconds left of the pop, or fade, after the heart got filled or emptied
+    animation: f32,
+}
+
+/// The lives past what fits in the row of hearts, or the misses in modes without lives.
+struct LivesCount;
+
+fn board_text(fonts: &Fonts) -> TextBundle {
+    TextBundle {
+        text: Text::with_section(
+            "",
+            TextStyle {
+                font: fonts.ui.clone(),
+                font_size: 40.0,
+                color: Color::rgb(0.0, 0.0, 0.0),
+            },
+            Default::default(),
+        ),
+        style: Style {
+            position_type: PositionType::Absolute,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+fn board_icon(material: Handle<ColorMaterial>) -> ImageBundle {
+    ImageBundle {
+        material,
+        style: Style {
+            position_type: PositionType::Absolute,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+fn spawn_boards(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+    theme: Res<Theme>,
+    fonts: Res<Fonts>,
+) {
+    // The second player's boards go below the rest, and stay hidden unless they play
+    for (number, top) in [(0, 5.0), (1, 235.0)].iter().copied() {
+        let color = Player(number).color();
+        commands
+            .spawn_bundle(board_icon(materials.add(ColorMaterial::modulated_texture(
+                asset_server.load(theme.star.as_str()),
+                color,
+            ))))
+            .insert(HudAnchor::top(5.0, top).with_size(Vec2::new(40.0, 40.0)))
+            .insert(BoardOwner(number))
+            .insert(Themed::Star);
+        commands
+            .spawn_bundle(board_text(&fonts))
+            .insert(RollingNumber::default())
+            .insert(BoardOwner(number))
+            .insert(HudAnchor::top(50.0, top).with_font_size(40.0));
+
+        // Each heart gets a material of its own, to fade on its own
+        for index in 0..HEART_ROW {
+            let material =
+                ColorMaterial::modulated_texture(asset_server.load(theme.heart.as_str()), color);
+            commands
+                .spawn_bundle(board_icon(materials.add(material)))
+                .insert(Heart {
+                    index,
+                    board_top: top,
+                    filled: false,
+                    slot: false,
+                    animation: 0.0,
+                })
+                .insert(BoardOwner(number))
+                .insert(Themed::Heart)
+                .insert(heart_anchor(index, top, HEART_SIZE));
+        }
+        commands
+            .spawn_bundle(board_text(&fonts))
+            .insert(LivesCount)
+            .insert(BoardOwner(number))
+            .insert(HudAnchor::top(40.0, top + 40.0).with_font_size(40.0));
+    }
+
+    commands
+        .spawn_bundle(board_icon(
+            materials.add(asset_server.load("high-score.png").into()),
+        ))
+        .insert(HudAnchor::top(5.0, 100.0).with_size(Vec2::new(40.0, 40.0)));
+    commands
+        .spawn_bundle(board_icon(
+            materials.add(asset_server.load(theme.star.as_str()).into()),
+        ))
+        .insert(HudAnchor::top(5.0, 145.0).with_size(Vec2::new(40.0, 40.0)))
+        .insert(Themed::Star);
+    commands
+        .spawn_bundle(board_text(&fonts))
+        .insert(BoardType::HighScore)
+        .insert(HudAnchor::top(50.0, 145.0).with_font_size(40.0));
+
+    commands
+        .spawn_bundle(board_text(&fonts))
+        .insert(BoardType::Countdown)
+        .insert(HudAnchor::top(5.0, 190.0).with_font_size(40.0));
+}
+
+/// Hearts grow from their center, so that popping ones stay in line with the others.
+fn heart_anchor(index: u64, board_top: f32, size: f32) -> HudAnchor {
+    let center = Vec2::new(
+        5.0 + HEART_SIZE / 2.0 + index as f32 * HEART_SPACING,
+        board_top + 46.0 + HEART_SIZE / 2.0,
+    );
+    HudAnchor::top(center.x - size / 2.0, center.y - size / 2.0).with_size(Vec2::new(size, size))
+}
+
+fn roll_scores(
+    time: Res<Time>,
+    players: Query<(&Player, &Scoreboard)>,
+    mut boards: Query<(&mut Text, &mut RollingNumber, &BoardOwner)>,
+) {
+    for (mut text, mut number, owner) in boards.iter_mut() {
+        let score = match players.iter().find(|(player, _)| player.0 == owner.0) {
+            Some((_, scoreboard)) => scoreboard.score() as f32,
+            None => continue,
+        };
+
+        // New games start from zero straight away rather than counting down to it
+        let gap = score - number.shown;
+        if score == 0.0 || gap.abs() < 0.5 {
+            number.shown = score;
+        } else {
+            let step = (ROLL_SPEED * time.delta_seconds()).min(1.0);
+            number.shown += gap.signum() * (gap.abs() * step).max(0.5).min(gap.abs());
+        }
+
+        let value = (number.shown.round() as u64).to_string();
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+fn update_hearts(
+    time: Res<Time>,
+    current_mode: Res<CurrentMode>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    players: Query<(&Player, &Lives)>,
+    mut hearts: Query<(
+        &mut Heart,
+        &BoardOwner,
+        &mut HudAnchor,
+        &mut Visible,
+        &Handle<ColorMaterial>,
+    )>,
+) {
+    for (mut heart, owner, mut anchor, mut visible, material) in hearts.iter_mut() {
+        let lives = players
+            .iter()
+            .find(|(player, _)| player.0 == owner.0)
+            .map(|(_, lives)| lives)
+            .filter(|_| current_mode.has_lives());
+
+        let (filled, slot) = match lives {
+            Some(lives) if lives.count() > HEART_ROW => (heart.index == 0, heart.index == 0),
+            Some(lives) => (
+                heart.index < lives.count(),
+                heart.index < lives.max().min(HEART_ROW),
+            ),
+            None => (false, false),
+        };
+        if filled != heart.filled {
+            heart.filled = filled;
+            heart.animation = HEART_ANIMATION;
+        } else if heart.animation > 0.0 {
+            heart.animation = (heart.animation - time.delta_seconds()).max(0.0);
+        }
+        heart.slot = slot;
+
+        let progress = heart.animation / HEART_ANIMATION;
+        let (alpha, size) = match (heart.filled, heart.slot) {
+            (true, _) => (1.0, HEART_SIZE * (1.0 + 0.5 * progress)),
+            (false, true) => (
+                EMPTY_HEART_ALPHA + (1.0 - EMPTY_HEART_ALPHA) * progress,
+                HEART_SIZE,
+            ),
+            (false, false) => (progress, HEART_SIZE),
+        };
+
+        let shown = alpha > 0.0;
+        if visible.is_visible != shown {
+            visible.is_visible = shown;
+        }
+        if anchor.size != Some(Vec2::new(size, size)) {
+            *anchor = heart_anchor(heart.index, heart.board_top, size);
+        }
+        let faded = materials.get(material).map_or(false, |material| {
+            (material.color.a() - alpha).abs() > f32::EPSILON
+        });
+        if let Some(material) = materials.get_mut(material).filter(|_| faded) {
+            material.color.set_a(alpha);
+        }
+    }
+}
+
+fn update_lives_count(
+    current_mode: Res<CurrentMode>,
+    players: Query<(&Player, &Lives, &Misses)>,
+    mut boards: Query<(&mut Text, &BoardOwner), With<LivesCount>>,
+) {
+    for (mut text, owner) in boards.iter_mut() {
+        let value = match players.iter().find(|(player, _, _)| player.0 == owner.0) {
+            // Misses take the place of lives when those are unlimited
+            Some((_, _, misses)) if !current_mode.has_lives() => format!("✗{}", misses.0),
+            Some((_, lives, _)) if lives.count() > HEART_ROW => format!("x {}", lives.count()),
+            _ => String::new(),
+        };
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+fn update_boards(
+    high_scores: Res<HighScores>,
+    current_mode: Res<CurrentMode>,
+    mut boards: Query<(&mut Text, &BoardType)>,
+) {
+    for (mut text, board_type) in boards.iter_mut() {
+        let value = match board_type {
+            BoardType::HighScore => high_scores.get(current_mode.mode).to_string(),
+            BoardType::Countdown => current_mode.time_left().map_or(String::new(), |left| {
+                let seconds = left.ceil() as u64;
+                format!("{}:{:02}", seconds / 60, seconds % 60)
+            }),
+        };
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+/// Hearts show themselves, depending on the lives left.
+fn show_player_boards(
+    players: Query<&Player>,
+    mut boards: Query<(&mut Visible, &BoardOwner), Without<Heart>>,
+) {
+    for (mut visible, owner) in boards.iter_mut() {
+        let playing = players.iter().any(|player| player.0 == owner.0);
+        if visible.is_visible != playing {
+            visible.is_visible = playing;
+        }
+    }
+}
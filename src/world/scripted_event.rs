@@ -0,0 +1,372 @@
+use bevy::prelude::*;
+use rand::Rng;
+use serde::Deserialize;
+
+use super::{
+    arena::{Arena, HudAnchor},
+    falling_object::{
+        spawn_object, ObjectKind, ObjectRegistry, SpawnRng, SpawnTable, SpawnWeight, Speed,
+    },
+    mode::CurrentMode,
+    player::{Player, Wind},
+    replay::GameClock,
+    scoreboard::Scoreboard,
+    theme::Theme,
+    EffectRequested, GameOver, GameStarted, GameState, NEW_GAME_LABEL,
+};
+use crate::{fonts::Fonts, localization::Localized, settings::Background};
+
+/// Every time the best score goes up by this much, the next scripted event starts.
+const MILESTONE: u64 = 40;
+const ANNOUNCEMENT_SECONDS: f32 = 3.0;
+
+const METEOR_SPEED_BOOST: f32 = 2.0;
+/// Seconds between the extra stars of a meteor shower, on top of the usual ones.
+const METEOR_INTERVAL: f32 = 0.4;
+const STORM_WIND: f32 = 2.5;
+const NIGHT_COLOR: Color = Color::rgb(0.25, 0.25, 0.45);
+
+/// Sets off meteor showers, storms and nights as the score goes up, or when objects ask for
+/// them.
+pub(super) struct ScriptedEventPlugin;
+
+impl Plugin for ScriptedEventPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(EventScheduler::default());
+        app.add_startup_system(spawn_announcement.system());
+        app.add_system(reset_scheduler.system().after(NEW_GAME_LABEL));
+        app.add_system(run_scheduler.system());
+        app.add_system(start_requested_events.system());
+        app.add_system(end_events.system());
+        app.add_system(darken_night.system());
+        app.add_system(show_announcement.system());
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScriptedEvent {
+    /// Only stars fall, more of them and faster
+    MeteorShower,
+    /// The clouds get pushed sideways
+    Storm,
+    /// The background darkens and the stars glow
+    Night,
+}
+
+/// The order events play out in, starting over once all of them have.
+static SCHEDULE: [ScriptedEvent; 3] = [
+    ScriptedEvent::MeteorShower,
+    ScriptedEvent::Storm,
+    ScriptedEvent::Night,
+];
+
+impl ScriptedEvent {
+    fn duration(self) -> f32 {
+        match self {
+            Self::MeteorShower => 10.0,
+            Self::Storm => 15.0,
+            Self::Night => 20.0,
+        }
+    }
+
+    fn announcement(self) -> &'static str {
+        match self {
+            Self::MeteorShower => "event-meteor-shower",
+            Self::Storm => "event-storm",
+            Self::Night => "event-night",
+        }
+    }
+}
+
+/// Which event is playing, and which one is up next.
+pub struct EventScheduler {
+    next_milestone: u64,
+    next_event: usize,
+    active: Option<ActiveEvent>,
+    announcement: Option<(ScriptedEvent, f32)>,
+}
+
+impl Default for EventScheduler {
+    fn default() -> Self {
+        Self {
+            next_milestone: MILESTONE,
+            next_event: 0,
+            active: None,
+            announcement: None,
+        }
+    }
+}
+
+impl EventScheduler {
+    pub fn active(&self) -> Option<ScriptedEvent> {
+        self.active.as_ref().map(|active| active.event)
+    }
+}
+
+struct ActiveEvent {
+    event: ScriptedEvent,
+    time_left: f32,
+    next_meteor: f32,
+    /// Put back once a meteor shower is over
+    replaced_spawn_table: Option<SpawnTable>,
+}
+
+impl ActiveEvent {
+    fn start(
+        event: ScriptedEvent,
+        speed: &mut Speed,
+        spawn_table: &mut SpawnTable,
+        wind: &mut Wind,
+        rng: &mut impl Rng,
+    ) -> Self {
+        let mut replaced_spawn_table = None;
+        match event {
+            ScriptedEvent::MeteorShower => {
+                speed.boost(METEOR_SPEED_BOOST);
+                let stars_only = SpawnTable(vec![SpawnWeight {
+                    kind: "star".to_string(),
+                    weight: 1,
+                }]);
+                replaced_spawn_table = Some(std::mem::replace(spawn_table, stars_only));
+            }
+            ScriptedEvent::Storm => {
+                wind.0 = if rng.gen_bool(0.5) {
+                    STORM_WIND
+                } else {
+                    -STORM_WIND
+                };
+            }
+            // Taken care of by `darken_night` for as long as it lasts
+            ScriptedEvent::Night => (),
+        }
+
+        Self {
+            event,
+            time_left: event.duration(),
+            next_meteor: METEOR_INTERVAL,
+            replaced_spawn_table,
+        }
+    }
+
+    fn end(self, speed: &mut Speed, spawn_table: &mut SpawnTable, wind: &mut Wind) {
+        match self.event {
+            ScriptedEvent::MeteorShower => speed.boost(-METEOR_SPEED_BOOST),
+            ScriptedEvent::Storm => wind.0 = 0.0,
+            ScriptedEvent::Night => (),
+        }
+        if let Some(replaced) = self.replaced_spawn_table {
+            *spawn_table = replaced;
+        }
+    }
+}
+
+fn reset_scheduler(
+    mut game_started: EventReader<GameStarted>,
+    mut scheduler: ResMut<EventScheduler>,
+) {
+    if game_started.iter().next().is_some() {
+        *scheduler = EventScheduler::default();
+    }
+}
+
+fn run_scheduler(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+    clock: Res<GameClock>,
+    game_state: Res<GameState>,
+    current_mode: Res<CurrentMode>,
+    arena: Res<Arena>,
+    theme: Res<Theme>,
+    registry: Res<ObjectRegistry>,
+    mut scheduler: ResMut<EventScheduler>,
+    mut speed: ResMut<Speed>,
+    mut spawn_table: ResMut<SpawnTable>,
+    mut wind: ResMut<Wind>,
+    mut spawn_rng: ResMut<SpawnRng>,
+    scoreboards: Query<&Scoreboard, With<Player>>,
+) {
+    if game_state.is_not_running() {
+        return;
+    }
+
+    let delta = clock.delta_seconds();
+    if let Some((_, time_left)) = scheduler.announcement.as_mut() {
+        *time_left -= delta;
+        if *time_left <= 0.0 {
+            scheduler.announcement = None;
+        }
+    }
+
+    if let Some(active) = scheduler.active.as_mut() {
+        active.time_left -= delta;
+
+        if active.event == ScriptedEvent::MeteorShower {
+            active.next_meteor -= delta;
+            if active.next_meteor <= 0.0 {
+                active.next_meteor += METEOR_INTERVAL;
+                let x = spawn_rng.rng().gen_range(arena.spawn_x_range());
+                spawn_object(
+                    &mut commands,
+                    &mut materials,
+                    &asset_server,
+                    &theme,
+                    &registry,
+                    ObjectKind::STAR,
+                    Vec2::new(x, arena.spawn_y),
+                );
+            }
+        }
+
+        if active.time_left <= 0.0 {
+            if let Some(active) = scheduler.active.take() {
+                active.end(&mut speed, &mut spawn_table, &mut wind);
+            }
+        }
+        return;
+    }
+
+    // A milestone passed during another event waits for it to end
+    let best_score = scoreboards.iter().map(|s| s.score()).max().unwrap_or(0);
+    if !current_mode.mode.has_scripted_events() || best_score < scheduler.next_milestone {
+        return;
+    }
+
+    let event = SCHEDULE[scheduler.next_event % SCHEDULE.len()];
+    scheduler.next_event += 1;
+    scheduler.next_milestone += MILESTONE;
+    scheduler.active = Some(ActiveEvent::start(
+        event,
+        &mut speed,
+        &mut spawn_table,
+        &mut wind,
+        spawn_rng.rng(),
+    ));
+    scheduler.announcement = Some((event, ANNOUNCEMENT_SECONDS));
+}
+
+/// Starts the events objects ask for, unless another one is already going. Those don't count
+/// towards the schedule, which carries on as before once they end.
+fn start_requested_events(
+    mut effect_requests: EventReader<EffectRequested>,
+    game_state: Res<GameState>,
+    mut scheduler: ResMut<EventScheduler>,
+    mut speed: ResMut<Speed>,
+    mut spawn_table: ResMut<SpawnTable>,
+    mut wind: ResMut<Wind>,
+    mut spawn_rng: ResMut<SpawnRng>,
+) {
+    for EffectRequested(event) in effect_requests.iter() {
+        if game_state.is_not_running() || scheduler.active.is_some() {
+            continue;
+        }
+
+        scheduler.active = Some(ActiveEvent::start(
+            *event,
+            &mut speed,
+            &mut spawn_table,
+            &mut wind,
+            spawn_rng.rng(),
+        ));
+        scheduler.announcement = Some((*event, ANNOUNCEMENT_SECONDS));
+    }
+}
+
+/// Whatever an event changed is put back once the game ends.
+fn end_events(
+    mut game_over: EventReader<GameOver>,
+    mut scheduler: ResMut<EventScheduler>,
+    mut speed: ResMut<Speed>,
+    mut spawn_table: ResMut<SpawnTable>,
+    mut wind: ResMut<Wind>,
+) {
+    if game_over.iter().next().is_none() {
+        return;
+    }
+
+    if let Some(active) = scheduler.active.take() {
+        active.end(&mut speed, &mut spawn_table, &mut wind);
+    }
+    scheduler.announcement = None;
+}
+
+/// Darkens the background at night, with the stars pulsing as they glow.
+fn darken_night(
+    time: Res<Time>,
+    scheduler: Res<EventScheduler>,
+    theme: Res<Theme>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    backgrounds: Query<&Handle<ColorMaterial>, With<Background>>,
+    mut objects: Query<(&ObjectKind, &mut Transform)>,
+) {
+    let night = scheduler.active() == Some(ScriptedEvent::Night);
+
+    let color = if night {
+        NIGHT_COLOR
+    } else {
+        theme.background_tint()
+    };
+    for handle in backgrounds.iter() {
+        if let Some(material) = materials.get_mut(handle) {
+            if material.color != color {
+                material.color = color;
+            }
+        }
+    }
+
+    let glow = 1.0 + 0.15 * (time.seconds_since_startup() as f32 * 6.0).sin();
+    for (kind, mut transform) in objects.iter_mut() {
+        let scale = match kind {
+            &ObjectKind::STAR if night => Vec3::new(glow, glow, 1.0),
+            _ => Vec3::ONE,
+        };
+        if transform.scale != scale {
+            transform.scale = scale;
+        }
+    }
+}
+
+struct Announcement;
+
+fn spawn_announcement(mut commands: Commands, fonts: Res<Fonts>) {
+    commands
+        .spawn_bundle(TextBundle {
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: fonts.ui.clone(),
+                    font_size: 36.0,
+                    color: Color::rgb_u8(230, 120, 40),
+                },
+                Default::default(),
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                ..Default::default()
+            },
+            visible: Visible {
+                is_visible: false,
+                is_transparent: true,
+            },
+            ..Default::default()
+        })
+        .insert(Localized("event-meteor-shower"))
+        .insert(Announcement)
+        .insert(HudAnchor::top(150.0, 250.0).with_font_size(36.0));
+}
+
+fn show_announcement(
+    scheduler: Res<EventScheduler>,
+    mut labels: Query<(&mut Visible, &mut Localized), With<Announcement>>,
+) {
+    for (mut visible, mut localized) in labels.iter_mut() {
+        visible.is_visible = scheduler.announcement.is_some();
+        if let Some((event, _)) = scheduler.announcement {
+            // Only replaced when different, so that the text isn't translated again every frame
+            if localized.0 != event.announcement() {
+                localized.0 = event.announcement();
+            }
+        }
+    }
+}
@@ -0,0 +1,478 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::*;
+use rand::Rng;
+use rhai::{Dynamic, Engine, Map, Scope, AST};
+
+use super::{
+    arena::Arena,
+    falling_object::{spawn_object, ObjectRegistry, SpawnRng, Speed},
+    mode::CurrentMode,
+    player::{take_life, Invulnerable, Player},
+    replay::GameClock,
+    scoreboard::{Lives, Scoreboard},
+    theme::Theme,
+    GameStarted, GameState, ObjectCaught, ObjectMissed, PlayerDied, PLAYER_DEATH_LABEL,
+};
+use crate::{embedded::AssetSource, settings::Settings};
+
+static RUN_SCRIPTS_LABEL: &str = "run_scripts_system";
+
+/// Operations each script may run every frame, all of its hooks together. Scripts which run
+/// more are stopped for good. Counting operations rather than time stops a script on the same
+/// frame on every machine, which replays and versus games rely on.
+const TICK_OPERATIONS: u64 = 100_000;
+/// How long a script may take in a frame before a warning is logged. It is never stopped for
+/// it, as that would depend on the machine.
+const TIME_LIMIT: Duration = Duration::from_millis(2);
+
+/// Runs the Rhai scripts in `assets/scripts`, which change the rules of the game by reacting to
+/// what happens in it.
+pub(super) struct ScriptingPlugin;
+
+impl Plugin for ScriptingPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let scripts = Scripts::load(app.world().get_resource::<AssetSource>().unwrap());
+        app.insert_resource(scripts);
+        app.add_system(run_scripts.system().label(RUN_SCRIPTS_LABEL));
+        app.add_system(
+            apply_script_commands
+                .system()
+                .after(RUN_SCRIPTS_LABEL)
+                .before(PLAYER_DEATH_LABEL),
+        );
+    }
+}
+
+/// What scripts ask for. Nothing they call changes the game right away, the commands are
+/// carried out once every script has had its turn.
+enum ScriptCommand {
+    AddPoints { player: i64, points: i64 },
+    AddLife { player: i64 },
+    RemoveLife { player: i64 },
+    SetSpeed(f32),
+    ChangeSpeed(f32),
+    Spawn { kind: String },
+}
+
+/// Everything shared between the game and the functions scripts can call.
+#[derive(Default)]
+struct ScriptApi {
+    commands: Vec<ScriptCommand>,
+    /// The number, score and lives of every player, as of the start of the frame
+    players: Vec<(usize, u64, u64)>,
+    speed: f32,
+}
+
+struct Script {
+    name: String,
+    ast: AST,
+    /// The names of the hooks the script defines
+    hooks: Vec<String>,
+    /// What the script keeps between calls, as `this`
+    state: Dynamic,
+    /// Set once the script has failed, or run out of operations
+    stopped: bool,
+}
+
+/// The operations a script has run in the current frame.
+#[derive(Default)]
+struct Budget {
+    /// Used up by the hooks which already returned
+    spent: u64,
+    /// Used up so far by the running hook
+    running: u64,
+}
+
+pub struct Scripts {
+    engine: Engine,
+    scripts: Vec<Script>,
+    api: Arc<Mutex<ScriptApi>>,
+    budget: Arc<Mutex<Budget>>,
+}
+
+impl Scripts {
+    fn new() -> Self {
+        let api = Arc::new(Mutex::new(ScriptApi::default()));
+        let budget = Arc::new(Mutex::new(Budget::default()));
+        Self {
+            engine: sandboxed_engine(&api, &budget),
+            scripts: Vec::new(),
+            api,
+            budget,
+        }
+    }
+
+    fn load(source: &AssetSource) -> Self {
+        let mut scripts = Self::new();
+        for path in source.read_dir(&PathBuf::from("scripts")) {
+            if path
+                .extension()
+                .map_or(true, |extension| extension != "rhai")
+            {
+                continue;
+            }
+            let contents = match source.read_to_string(&path) {
+                Some(contents) => contents,
+                None => {
+                    warn!("Could not load {}", path.display());
+                    continue;
+                }
+            };
+
+            scripts.add(path.display().to_string(), &contents);
+        }
+        scripts
+    }
+
+    /// Compiles a script, which is left out if it isn't valid or uses what the sandbox forbids.
+    fn add(&mut self, name: String, contents: &str) {
+        match self.engine.compile(contents) {
+            Ok(ast) => {
+                info!("Loaded script {}", name);
+                self.scripts.push(Script {
+                    hooks: ast
+                        .iter_functions()
+                        .map(|function| function.name.to_string())
+                        .collect(),
+                    name,
+                    ast,
+                    state: Dynamic::from(Map::new()),
+                    stopped: false,
+                });
+            }
+            Err(error) => warn!("Could not load script {}: {}", name, error),
+        }
+    }
+
+    /// Calls each hook in `calls` on every script defining it, in order.
+    fn call_hooks(&mut self, calls: &[(&str, Vec<Dynamic>)]) {
+        for script in self.scripts.iter_mut().filter(|script| !script.stopped) {
+            *self.budget.lock().unwrap() = Budget::default();
+            let started = Instant::now();
+
+            for (hook, arguments) in calls {
+                if !script.hooks.iter().any(|name| name == hook) {
+                    continue;
+                }
+
+                let result = self.engine.call_fn_dynamic(
+                    &mut Scope::new(),
+                    &script.ast,
+                    false,
+                    hook,
+                    Some(&mut script.state),
+                    arguments.clone(),
+                );
+
+                let mut budget = self.budget.lock().unwrap();
+                budget.spent += budget.running;
+                budget.running = 0;
+
+                if let Err(error) = result {
+                    warn!("Stopped script {} in {}: {}", script.name, hook, error);
+                    script.stopped = true;
+                    break;
+                }
+            }
+
+            let took = started.elapsed();
+            if took > TIME_LIMIT {
+                warn!("Script {} took {:?} in a single frame", script.name, took);
+            }
+        }
+    }
+
+    /// Scripts start every game from scratch.
+    fn reset(&mut self) {
+        for script in self.scripts.iter_mut() {
+            script.state = Dynamic::from(Map::new());
+        }
+    }
+}
+
+/// An engine which can't reach outside of the game: scripts can't load modules or evaluate
+/// code of their own, only call the functions registered here, and get stopped once they use
+/// up the operations of the frame or grow too big.
+fn sandboxed_engine(api: &Arc<Mutex<ScriptApi>>, budget: &Arc<Mutex<Budget>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_call_levels(32);
+    engine.set_max_expr_depths(64, 32);
+    engine.set_max_string_size(4096);
+    engine.set_max_array_size(1024);
+    engine.set_max_map_size(1024);
+    engine.disable_symbol("eval");
+
+    // Rhai counts the operations of every call from zero.
+    let budget = Arc::clone(budget);
+    engine.on_progress(move |operations| {
+        let mut budget = budget.lock().unwrap();
+        budget.running = operations;
+        if budget.spent + operations > TICK_OPERATIONS {
+            Some(Dynamic::UNIT)
+        } else {
+            None
+        }
+    });
+    engine.on_print(|text| info!("Script: {}", text));
+    engine.on_debug(|text, _, position| info!("Script at {}: {}", position, text));
+
+    let shared = Arc::clone(api);
+    engine.register_fn("add_points", move |player: i64, points: i64| {
+        shared
+            .lock()
+            .unwrap()
+            .commands
+            .push(ScriptCommand::AddPoints { player, points });
+    });
+    let shared = Arc::clone(api);
+    engine.register_fn("add_life", move |player: i64| {
+        shared
+            .lock()
+            .unwrap()
+            .commands
+            .push(ScriptCommand::AddLife { player });
+    });
+    let shared = Arc::clone(api);
+    engine.register_fn("remove_life", move |player: i64| {
+        shared
+            .lock()
+            .unwrap()
+            .commands
+            .push(ScriptCommand::RemoveLife { player });
+    });
+    let shared = Arc::clone(api);
+    engine.register_fn("set_speed", move |speed: f64| {
+        shared
+            .lock()
+            .unwrap()
+            .commands
+            .push(ScriptCommand::SetSpeed(speed as f32));
+    });
+    let shared = Arc::clone(api);
+    engine.register_fn("change_speed", move |amount: f64| {
+        shared
+            .lock()
+            .unwrap()
+            .commands
+            .push(ScriptCommand::ChangeSpeed(amount as f32));
+    });
+    let shared = Arc::clone(api);
+    engine.register_fn("spawn", move |kind: &str| {
+        let kind = kind.to_string();
+        shared
+            .lock()
+            .unwrap()
+            .commands
+            .push(ScriptCommand::Spawn { kind });
+    });
+
+    let shared = Arc::clone(api);
+    engine.register_fn("score", move |player: i64| {
+        let api = shared.lock().unwrap();
+        let found = api
+            .players
+            .iter()
+            .find(|(number, ..)| *number as i64 == player);
+        found.map_or(0, |(_, score, _)| *score as i64)
+    });
+    let shared = Arc::clone(api);
+    engine.register_fn("lives", move |player: i64| {
+        let api = shared.lock().unwrap();
+        let found = api
+            .players
+            .iter()
+            .find(|(number, ..)| *number as i64 == player);
+        found.map_or(0, |(_, _, lives)| *lives as i64)
+    });
+    let shared = Arc::clone(api);
+    engine.register_fn("speed", move || shared.lock().unwrap().speed as f64);
+
+    engine
+}
+
+/// Calls the hooks of the scripts for whatever happened since the last frame: `on_start()`,
+/// `on_catch(player, kind)`, `on_miss(player, kind)`, `on_death(player)` and, while the game
+/// runs, `on_tick(seconds)`.
+fn run_scripts(
+    clock: Res<GameClock>,
+    game_state: Res<GameState>,
+    speed: Res<Speed>,
+    registry: Res<ObjectRegistry>,
+    mut scripts: ResMut<Scripts>,
+    mut game_started: EventReader<GameStarted>,
+    mut object_caught: EventReader<ObjectCaught>,
+    mut object_missed: EventReader<ObjectMissed>,
+    mut player_died: EventReader<PlayerDied>,
+    players: Query<(&Player, &Scoreboard, &Lives)>,
+) {
+    if scripts.scripts.is_empty() {
+        return;
+    }
+
+    {
+        let mut api = scripts.api.lock().unwrap();
+        api.players = players
+            .iter()
+            .map(|(player, scoreboard, lives)| (player.0, scoreboard.score(), lives.count()))
+            .collect();
+        api.speed = speed.value();
+    }
+
+    let mut calls: Vec<(&str, Vec<Dynamic>)> = Vec::new();
+    if game_started.iter().next().is_some() {
+        scripts.reset();
+        calls.push(("on_start", Vec::new()));
+    }
+    for caught in object_caught.iter() {
+        let kind = registry.get(caught.kind).name.clone();
+        calls.push((
+            "on_catch",
+            vec![Dynamic::from(caught.player as i64), Dynamic::from(kind)],
+        ));
+    }
+    for missed in object_missed.iter() {
+        let kind = registry.get(missed.kind).name.clone();
+        calls.push((
+            "on_miss",
+            vec![Dynamic::from(missed.player as i64), Dynamic::from(kind)],
+        ));
+    }
+    for died in player_died.iter() {
+        calls.push(("on_death", vec![Dynamic::from(died.player as i64)]));
+    }
+    if *game_state == GameState::Running {
+        calls.push(("on_tick", vec![Dynamic::from(clock.delta_seconds() as f64)]));
+    }
+
+    if !calls.is_empty() {
+        scripts.call_hooks(&calls);
+    }
+}
+
+fn apply_script_commands(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+    theme: Res<Theme>,
+    arena: Res<Arena>,
+    registry: Res<ObjectRegistry>,
+    current_mode: Res<CurrentMode>,
+    settings: Res<Settings>,
+    scripts: Res<Scripts>,
+    mut speed: ResMut<Speed>,
+    mut spawn_rng: ResMut<SpawnRng>,
+    mut player_died: EventWriter<PlayerDied>,
+    mut players: Query<(&Player, &mut Scoreboard, &mut Lives, &mut Invulnerable)>,
+) {
+    let queued = std::mem::take(&mut scripts.api.lock().unwrap().commands);
+
+    for command in queued {
+        let target = match command {
+            ScriptCommand::AddPoints { player, .. }
+            | ScriptCommand::AddLife { player }
+            | ScriptCommand::RemoveLife { player } => Some(player),
+            _ => None,
+        };
+        let found = target.and_then(|target| {
+            players
+                .iter_mut()
+                .find(|(player, ..)| player.0 as i64 == target)
+        });
+
+        match (command, found) {
+            (ScriptCommand::AddPoints { points, .. }, Some((_, mut scoreboard, _, _))) => {
+                scoreboard.add_points(points);
+            }
+            (ScriptCommand::AddLife { .. }, Some((_, _, mut lives, _))) => {
+                if !lives.is_full() {
+                    lives.add_life();
+                }
+            }
+            (ScriptCommand::RemoveLife { .. }, Some((player, _, mut lives, mut invulnerable))) => {
+                if current_mode.has_lives() {
+                    take_life(
+                        player,
+                        &mut lives,
+                        &mut invulnerable,
+                        settings.grace_period,
+                        &mut player_died,
+                    );
+                }
+            }
+            (ScriptCommand::SetSpeed(value), _) => speed.set(value),
+            (ScriptCommand::ChangeSpeed(amount), _) => speed.change(amount),
+            (ScriptCommand::Spawn { kind }, _) => match registry.find(&kind) {
+                Some(kind) => {
+                    let x = spawn_rng.rng().gen_range(arena.spawn_x_range());
+                    spawn_object(
+                        &mut commands,
+                        &mut materials,
+                        &asset_server,
+                        &theme,
+                        &registry,
+                        kind,
+                        Vec2::new(x, arena.spawn_y),
+                    );
+                }
+                None => warn!("Scripts can't spawn {}, there is no such object", kind),
+            },
+            // Players who aren't playing
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stops_a_script_which_never_returns() {
+        let mut scripts = Scripts::new();
+        scripts.add("endless".to_string(), "fn on_tick(seconds) { loop {} }");
+        scripts.add(
+            "quick".to_string(),
+            "fn on_tick(seconds) { change_speed(seconds) }",
+        );
+
+        let calls = [("on_tick", vec![Dynamic::from(0.5_f64)])];
+        scripts.call_hooks(&calls);
+        scripts.call_hooks(&calls);
+
+        assert!(scripts.scripts[0].stopped);
+        assert!(!scripts.scripts[1].stopped);
+        assert_eq!(scripts.api.lock().unwrap().commands.len(), 2);
+    }
+
+    #[test]
+    fn gives_every_frame_a_new_budget() {
+        let mut scripts = Scripts::new();
+        let contents = format!(
+            "fn on_tick(seconds) {{ for i in range(0, {}) {{}} }}",
+            TICK_OPERATIONS / 10
+        );
+        scripts.add("busy".to_string(), &contents);
+
+        for _ in 0..20 {
+            scripts.call_hooks(&[("on_tick", vec![Dynamic::from(0.5_f64)])]);
+        }
+
+        assert!(!scripts.scripts[0].stopped);
+    }
+
+    #[test]
+    fn rejects_eval() {
+        let mut scripts = Scripts::new();
+        scripts.add(
+            "eval".to_string(),
+            "fn on_start() { eval(\"add_life(1)\") }",
+        );
+
+        assert!(scripts.scripts.is_empty());
+    }
+}
@@ -0,0 +1,197 @@
+mod screen;
+
+pub use screen::StatsScreen;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    falling_object::{ObjectKind, Speed},
+    mode::CurrentMode,
+    replay::GameClock,
+    GameOver, GameStarted, GameState, ObjectCaught, ObjectMissed, NEW_GAME_LABEL,
+    PLAYER_DEATH_LABEL,
+};
+use crate::storage;
+
+/// How many of the latest games are kept for the median and the histograms.
+const RECENT_GAMES: usize = 500;
+
+/// Keeps count of everything that happens in games, for this game, this session and all time.
+pub(super) struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(LifetimeStats::load_from_file());
+        app.insert_resource(GameStats::default());
+        app.insert_resource(SessionStats::default());
+
+        app.add_system(
+            track_game
+                .system()
+                .after(NEW_GAME_LABEL)
+                .before(PLAYER_DEATH_LABEL),
+        );
+        app.add_system(record_game.system().after(PLAYER_DEATH_LABEL));
+        app.add_plugin(screen::StatsScreenPlugin);
+    }
+}
+
+/// Totals over every scored game, stored as `stats.toml` in the user's data directory.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LifetimeStats {
+    pub stars_caught: u64,
+    pub stars_missed: u64,
+    pub hearts_caught: u64,
+    pub peak_speed: f32,
+    pub games_played: u64,
+    pub total_score: u64,
+    pub longest_game: f32,
+    /// The latest games, for the median and the histograms
+    pub games: Vec<PlayedGame>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct PlayedGame {
+    pub score: u64,
+    pub seconds: f32,
+}
+
+impl LifetimeStats {
+    pub fn load_from_file() -> Self {
+        let mut stats: Self = storage::load_toml(storage::data_file("stats.toml"), "stats");
+
+        // Older files kept every game and no totals
+        if stats.games_played < stats.games.len() as u64 {
+            stats.games_played = stats.games.len() as u64;
+            stats.total_score = stats.games.iter().map(|game| game.score).sum();
+            stats.longest_game = stats
+                .games
+                .iter()
+                .map(|game| game.seconds)
+                .fold(0.0, f32::max);
+        }
+        let excess = stats.games.len().saturating_sub(RECENT_GAMES);
+        stats.games.drain(..excess);
+        stats
+    }
+
+    pub fn update_file(&self) {
+        storage::save_toml(storage::data_file("stats.toml"), "stats", self);
+    }
+
+    pub fn add(&mut self, game: &GameStats, score: u64) {
+        self.stars_caught += game.stars_caught;
+        self.stars_missed += game.stars_missed;
+        self.hearts_caught += game.hearts_caught;
+        self.peak_speed = self.peak_speed.max(game.peak_speed);
+        self.games_played += 1;
+        self.total_score += score;
+        self.longest_game = self.longest_game.max(game.seconds);
+
+        if self.games.len() >= RECENT_GAMES {
+            self.games.remove(0);
+        }
+        self.games.push(PlayedGame {
+            score,
+            seconds: game.seconds,
+        });
+    }
+
+    pub fn average_score(&self) -> f64 {
+        if self.games_played == 0 {
+            return 0.0;
+        }
+
+        self.total_score as f64 / self.games_played as f64
+    }
+
+    pub fn median_score(&self) -> f64 {
+        let mut scores: Vec<u64> = self.games.iter().map(|game| game.score).collect();
+        scores.sort_unstable();
+
+        match scores.len() {
+            0 => 0.0,
+            count if count % 2 == 0 => (scores[count / 2 - 1] + scores[count / 2]) as f64 / 2.0,
+            count => scores[count / 2] as f64,
+        }
+    }
+}
+
+/// What happened in the game being played, or the last one.
+#[derive(Clone, Default)]
+pub struct GameStats {
+    pub stars_caught: u64,
+    pub stars_missed: u64,
+    pub hearts_caught: u64,
+    pub seconds: f32,
+    pub peak_speed: f32,
+}
+
+/// Everything played since the game was launched.
+#[derive(Default)]
+pub struct SessionStats {
+    pub games: u64,
+    pub best_score: u64,
+}
+
+fn track_game(
+    clock: Res<GameClock>,
+    game_state: Res<GameState>,
+    speed: Res<Speed>,
+    mut game: ResMut<GameStats>,
+    mut game_started: EventReader<GameStarted>,
+    mut object_caught: EventReader<ObjectCaught>,
+    mut object_missed: EventReader<ObjectMissed>,
+) {
+    if game_started.iter().next().is_some() {
+        *game = GameStats::default();
+    }
+
+    for caught in object_caught.iter() {
+        match caught.kind {
+            ObjectKind::STAR => game.stars_caught += 1,
+            ObjectKind::HEART => game.hearts_caught += 1,
+            _ => (),
+        }
+    }
+    for missed in object_missed.iter() {
+        if missed.kind == ObjectKind::STAR {
+            game.stars_missed += 1;
+        }
+    }
+
+    if !game_state.is_not_running() {
+        game.seconds += clock.delta_seconds();
+        game.peak_speed = game.peak_speed.max(speed.value());
+    }
+}
+
+fn record_game(
+    mut game_over: EventReader<GameOver>,
+    current_mode: Res<CurrentMode>,
+    game: Res<GameStats>,
+    mut session: ResMut<SessionStats>,
+    mut lifetime: ResMut<LifetimeStats>,
+) {
+    let score = match game_over.iter().next() {
+        Some(game_over) => game_over.score,
+        None => return,
+    };
+
+    session.games += 1;
+    session.best_score = session.best_score.max(score);
+
+    // Like high scores, lifetime stats only count real games
+    if !current_mode.is_practice() {
+        lifetime.add(&game, score);
+        lifetime.update_file();
+    }
+}
+
+/// Seconds as `m:ss`.
+pub fn format_time(seconds: f32) -> String {
+    let seconds = seconds as u64;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
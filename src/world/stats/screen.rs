@@ -0,0 +1,195 @@
+use bevy::prelude::*;
+
+use super::{format_time, LifetimeStats};
+use crate::{
+    fonts::Fonts,
+    localization::Localization,
+    settings::SettingsScreen,
+    world::{ArenaViewport, GameState, HudAnchor},
+};
+
+/// How many bars each histogram is split into, at most.
+const BUCKETS: u64 = 6;
+/// How many characters the longest bar takes up.
+const BAR_WIDTH: usize = 16;
+
+pub(super) struct StatsScreenPlugin;
+
+impl Plugin for StatsScreenPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(StatsScreen::default());
+        app.add_system(toggle_stats.system());
+        app.add_system(render_stats.system());
+    }
+}
+
+/// An overlay with the lifetime stats. Opened with F3 between games.
+#[derive(Default)]
+pub struct StatsScreen {
+    open: bool,
+}
+
+impl StatsScreen {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+}
+
+fn toggle_stats(
+    keyboard_input: Res<Input<KeyCode>>,
+    game_state: Res<GameState>,
+    settings_screen: Res<SettingsScreen>,
+    mut screen: ResMut<StatsScreen>,
+) {
+    // Starting a game, or opening the settings, gets it out of the way
+    if screen.open && (!game_state.is_stopped() || settings_screen.is_open()) {
+        screen.open = false;
+    } else if keyboard_input.just_pressed(KeyCode::F3)
+        && game_state.is_stopped()
+        && !settings_screen.is_open()
+    {
+        screen.open = !screen.open;
+    }
+}
+
+/// One line per bar, counting the values which fall in each range. Ranges are as wide as
+/// needed to fit every value in [`BUCKETS`] of them.
+fn histogram(values: &[u64], label: impl Fn(u64) -> String) -> Vec<String> {
+    let max = match values.iter().max() {
+        Some(max) => *max,
+        None => return Vec::new(),
+    };
+
+    let width = max / BUCKETS + 1;
+    let mut counts = vec![0; (max / width + 1) as usize];
+    for value in values {
+        counts[(value / width) as usize] += 1;
+    }
+
+    let labels: Vec<String> = (0..counts.len() as u64)
+        .map(|bucket| label(bucket * width))
+        .collect();
+    let label_width = labels
+        .iter()
+        .map(|label| label.chars().count())
+        .max()
+        .unwrap_or(0);
+    let highest = counts.iter().copied().max().unwrap_or(1);
+
+    labels
+        .iter()
+        .zip(counts)
+        .map(|(label, count)| {
+            let bar = "█".repeat((count * BAR_WIDTH + highest - 1) / highest);
+            format!("{:>width$} {} {}", label, bar, count, width = label_width)
+        })
+        .collect()
+}
+
+struct StatsPanel;
+struct StatsText;
+
+fn render_stats(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    screen: Res<StatsScreen>,
+    stats: Res<LifetimeStats>,
+    localization: Res<Localization>,
+    viewport: Res<ArenaViewport>,
+    fonts: Res<Fonts>,
+    panels: Query<Entity, With<StatsPanel>>,
+    mut texts: Query<&mut Text, With<StatsText>>,
+) {
+    if !screen.open {
+        for panel in panels.iter() {
+            commands.entity(panel).despawn_recursive();
+        }
+        return;
+    }
+
+    let needs_redraw = screen.is_changed()
+        || stats.is_changed()
+        || localization.is_changed()
+        || viewport.is_changed();
+    if !needs_redraw {
+        return;
+    }
+
+    let font_size = 17.0 * viewport.pixels_per_unit;
+    let section = |value: String, color: Color| TextSection {
+        value,
+        style: TextStyle {
+            font: fonts.ui.clone(),
+            font_size,
+            color,
+        },
+    };
+
+    let totals = [
+        ("stats-games-played", stats.games_played.to_string()),
+        ("stats-stars-caught", stats.stars_caught.to_string()),
+        ("stats-stars-missed", stats.stars_missed.to_string()),
+        ("stats-hearts-caught", stats.hearts_caught.to_string()),
+        (
+            "stats-average-score",
+            format!("{:.1}", stats.average_score()),
+        ),
+        ("stats-median-score", format!("{:.1}", stats.median_score())),
+        ("stats-longest-game", format_time(stats.longest_game)),
+        ("stats-peak-speed", format!("{:.2}", stats.peak_speed)),
+    ];
+    let mut lines: Vec<String> = totals
+        .iter()
+        .map(|(key, value)| format!("{}: {}", localization.get(key), value))
+        .collect();
+
+    let scores: Vec<u64> = stats.games.iter().map(|game| game.score).collect();
+    let lengths: Vec<u64> = stats.games.iter().map(|game| game.seconds as u64).collect();
+    if !scores.is_empty() {
+        lines.push(format!("\n{}", localization.get("stats-scores-histogram")));
+        lines.extend(histogram(&scores, |score| score.to_string()));
+        lines.push(format!("\n{}", localization.get("stats-lengths-histogram")));
+        lines.extend(histogram(&lengths, |seconds| format_time(seconds as f32)));
+    }
+
+    let sections = vec![
+        section(
+            localization.get("stats-title") + "\n\n",
+            Color::rgb(0.0, 0.0, 0.0),
+        ),
+        section(lines.join("\n") + "\n", Color::rgb(0.0, 0.0, 0.0)),
+        section(
+            format!("\n{}", localization.get("stats-hint")),
+            Color::rgb(0.3, 0.3, 0.3),
+        ),
+    ];
+
+    if let Some(mut text) = texts.iter_mut().next() {
+        text.sections = sections;
+        return;
+    }
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                padding: Rect::all(Val::Px(10.0)),
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(1.0, 1.0, 1.0, 0.92).into()),
+            ..Default::default()
+        })
+        .insert(HudAnchor::top(20.0, 40.0).with_size(Vec2::new(500.0, 520.0)))
+        .insert(StatsPanel)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text {
+                        sections,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .insert(StatsText);
+        });
+}
@@ -0,0 +1,261 @@
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+use toml::value::{Table, Value};
+
+use super::{arena::Wall, scoreboard::HighScores};
+use crate::{embedded::AssetSource, settings::Settings};
+
+/// The themes which can be picked in the settings, each read from `assets/themes/<name>.toml`.
+/// The day theme comes first, the others build on it.
+static THEMES: [&str; 4] = ["day", "night", "space", "underwater"];
+
+/// Swaps the sprites and colors of the game for those of the theme picked in the settings.
+pub(super) struct ThemePlugin;
+
+impl Plugin for ThemePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let world = app.world();
+        let themes = Themes::load(world.get_resource::<AssetSource>().unwrap());
+        let theme = themes.pick(
+            &world.get_resource::<Settings>().unwrap().theme,
+            world.get_resource::<HighScores>().unwrap(),
+        );
+        app.insert_resource(theme);
+        app.insert_resource(themes);
+        app.add_system(switch_theme.system());
+        app.add_system(restyle.system());
+    }
+}
+
+/// The look of the game. Any value missing from a theme's file is taken from the day theme's
+/// file, or from [`Theme::default`] when that one leaves it out too.
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub background: String,
+    pub background_tint: [u8; 3],
+    pub cloud: String,
+    pub star: String,
+    pub heart: String,
+    pub wall_color: [u8; 3],
+    /// Drawn over the background, the furthest first
+    pub layers: Vec<ParallaxLayer>,
+    /// The high score, in any mode, needed before the theme can be picked
+    pub unlock_score: u64,
+    /// The name the theme was loaded by
+    #[serde(skip)]
+    pub name: String,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            background: "backgroun2d.png".to_string(),
+            background_tint: [255, 255, 255],
+            cloud: "cloud.png".to_string(),
+            star: "star.png".to_string(),
+            heart: "heart.png".to_string(),
+            wall_color: [146, 208, 209],
+            layers: vec![ParallaxLayer {
+                sprite: "cloud.png".to_string(),
+                count: 4,
+                size: [58.0, 28.0],
+                speed: 0.3,
+                drift: 0.1,
+                alpha: 0.5,
+                ..Default::default()
+            }],
+            unlock_score: 0,
+            name: "day".to_string(),
+        }
+    }
+}
+
+impl Theme {
+    /// The values in the file of the theme called `name`, if it can be read.
+    fn read(source: &AssetSource, name: &str) -> Option<Table> {
+        let path = PathBuf::from("themes").join(name).with_extension("toml");
+
+        let contents = match source.read_to_string(&path) {
+            Some(contents) => contents,
+            None => {
+                warn!("Could not load {}", path.display());
+                return None;
+            }
+        };
+        toml::from_str(&contents)
+            .map_err(|error| warn!("Could not load theme {}: {}", path.display(), error))
+            .ok()
+    }
+
+    /// Builds the theme called `name` out of its `file` laid over the `day` theme's, falling
+    /// back to the built-in day theme if the result isn't a valid theme.
+    fn from_file(name: &str, file: Option<Table>, day: &Table) -> Self {
+        let mut values = day.clone();
+        values.extend(file.unwrap_or_default());
+
+        let theme = Value::Table(values).try_into().unwrap_or_else(|error| {
+            warn!("Could not load theme {}: {}", name, error);
+            Self::default()
+        });
+        Self {
+            name: name.to_string(),
+            ..theme
+        }
+    }
+
+    pub fn background_tint(&self) -> Color {
+        let [r, g, b] = self.background_tint;
+        Color::rgb_u8(r, g, b)
+    }
+
+    pub fn wall_color(&self) -> Color {
+        let [r, g, b] = self.wall_color;
+        Color::rgb_u8(r, g, b)
+    }
+}
+
+/// Sprites scattered over the background, which scroll down slower than the falling objects
+/// do, the further away they are.
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct ParallaxLayer {
+    pub sprite: String,
+    /// How many copies of the sprite the layer is made of
+    pub count: usize,
+    /// Width and height of each copy, in arena units
+    pub size: [f32; 2],
+    /// How fast the layer scrolls down, as a fraction of how fast objects fall
+    pub speed: f32,
+    /// Arena units the layer moves sideways every frame, while it scrolls
+    pub drift: f32,
+    pub tint: [u8; 3],
+    /// From 0.0 to 1.0
+    pub alpha: f32,
+    /// Whether the copies fade in and out, like distant stars
+    pub twinkle: bool,
+}
+
+impl Default for ParallaxLayer {
+    fn default() -> Self {
+        Self {
+            sprite: "star.png".to_string(),
+            count: 10,
+            size: [10.0, 10.0],
+            speed: 0.1,
+            drift: 0.0,
+            tint: [255, 255, 255],
+            alpha: 1.0,
+            twinkle: false,
+        }
+    }
+}
+
+impl ParallaxLayer {
+    pub fn color(&self) -> Color {
+        let [r, g, b] = self.tint;
+        Color::rgba_u8(r, g, b, (self.alpha.clamp(0.0, 1.0) * 255.0) as u8)
+    }
+}
+
+/// Every theme in [`THEMES`], whether it has been unlocked or not.
+pub struct Themes(Vec<Theme>);
+
+impl Themes {
+    fn load(source: &AssetSource) -> Self {
+        let files: Vec<Option<Table>> = THEMES
+            .iter()
+            .map(|name| Theme::read(source, name))
+            .collect();
+        let day = files[0].clone().unwrap_or_default();
+        Self(
+            THEMES
+                .iter()
+                .zip(files)
+                .map(|(name, file)| Theme::from_file(name, file, &day))
+                .collect(),
+        )
+    }
+
+    /// The names of the themes the high scores are good enough for, in the order of
+    /// [`THEMES`].
+    pub fn unlocked(&self, high_scores: &HighScores) -> Vec<String> {
+        self.0
+            .iter()
+            .filter(|theme| high_scores.best() >= theme.unlock_score)
+            .map(|theme| theme.name.clone())
+            .collect()
+    }
+
+    /// The theme called `name`, or the day theme while it is locked.
+    fn pick(&self, name: &str, high_scores: &HighScores) -> Theme {
+        self.0
+            .iter()
+            .find(|theme| theme.name == name && high_scores.best() >= theme.unlock_score)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// Marks the sprites which change with the theme.
+pub enum Themed {
+    Background,
+    Cloud,
+    Star,
+    Heart,
+}
+
+fn switch_theme(
+    settings: Res<Settings>,
+    high_scores: Res<HighScores>,
+    themes: Res<Themes>,
+    mut theme: ResMut<Theme>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    let picked = themes.pick(&settings.theme, &high_scores);
+    if picked.name != theme.name {
+        *theme = picked;
+    }
+}
+
+/// Things spawned from now on pick up the theme by themselves, this takes care of those
+/// already around.
+fn restyle(
+    theme: Res<Theme>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    themed: Query<(&Themed, &Handle<ColorMaterial>)>,
+    walls: Query<&Handle<ColorMaterial>, With<Wall>>,
+) {
+    if !theme.is_changed() || theme.is_added() {
+        return;
+    }
+
+    for (themed, handle) in themed.iter() {
+        let material = match materials.get_mut(handle) {
+            Some(material) => material,
+            None => continue,
+        };
+        let sprite = match themed {
+            Themed::Background => {
+                material.color = theme.background_tint();
+                &theme.background
+            }
+            Themed::Cloud => &theme.cloud,
+            Themed::Star => &theme.star,
+            Themed::Heart => &theme.heart,
+        };
+        material.texture = Some(asset_server.load(sprite.as_str()));
+    }
+
+    for handle in walls.iter() {
+        if let Some(material) = materials.get_mut(handle) {
+            material.color = theme.wall_color();
+        }
+    }
+}
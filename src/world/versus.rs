@@ -0,0 +1,447 @@
+mod protocol;
+
+pub use protocol::*;
+
+use bevy::prelude::*;
+use rand::{thread_rng, Rng};
+use std::{
+    io::{ErrorKind, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    time::Duration,
+};
+
+use super::{
+    arena::{Arena, HudAnchor},
+    falling_object::{spawn_object, ObjectKind, ObjectRegistry},
+    player::Player,
+    scoreboard::{Combo, Lives, Scoreboard},
+    theme::Theme,
+    GameOver, GameStarted, GameState, PLAYER_DEATH_LABEL,
+};
+use crate::{fonts::Fonts, localization::Localization, settings::Difficulty};
+
+/// How many stars have to be caught in a row to drop one on the opponent.
+const COMBO_FOR_GARBAGE: u64 = 5;
+
+/// Plays against another instance of the game when a [`Versus`] connection has been inserted
+/// before the plugin is added. Does nothing otherwise.
+pub(super) struct VersusPlugin;
+
+impl Plugin for VersusPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        if app.world().get_resource::<Versus>().is_none() {
+            return;
+        }
+
+        app.add_startup_system(spawn_opponent_board.system());
+        app.add_system(exchange_messages.system().after(PLAYER_DEATH_LABEL));
+        app.add_system(update_opponent_board.system());
+    }
+}
+
+/// The connection to the opponent, along with what they last told us.
+pub struct Versus {
+    stream: TcpStream,
+    received: Vec<u8>,
+    /// What couldn't be sent yet without blocking the game
+    pending: Vec<u8>,
+    connected: bool,
+    sent_state: Option<(u64, u64)>,
+    lost: bool,
+    opponent: Opponent,
+}
+
+#[derive(Default)]
+pub struct Opponent {
+    pub score: u64,
+    pub lives: u64,
+    pub lost: bool,
+}
+
+impl Versus {
+    /// Waits for an opponent to connect to `address`, then tells them which run to play.
+    pub fn host(address: SocketAddr, seed: u64, difficulty: Difficulty) -> Result<Self, String> {
+        let listener = TcpListener::bind(address).map_err(|e| e.to_string())?;
+        eprintln!("Waiting for an opponent on {}", address);
+        Self::accept(&listener, seed, difficulty)
+    }
+
+    fn accept(listener: &TcpListener, seed: u64, difficulty: Difficulty) -> Result<Self, String> {
+        let (stream, address) = listener.accept().map_err(|e| e.to_string())?;
+        eprintln!("Playing against {}", address);
+
+        let mut versus = Self::new(stream)?;
+        versus.send(&Message::Hello {
+            version: PROTOCOL_VERSION,
+            game: Some((seed, difficulty)),
+        });
+        match versus.wait_for_message()? {
+            Message::Hello { version, .. } => check_version(version)?,
+            _ => return Err("the opponent didn't say hello".to_string()),
+        }
+
+        versus.start_playing()?;
+        Ok(versus)
+    }
+
+    /// Connects to a hosting opponent, returning the seed and difficulty of the run they
+    /// picked.
+    pub fn join(address: &str) -> Result<(Self, u64, Difficulty), String> {
+        let stream = TcpStream::connect(address).map_err(|e| e.to_string())?;
+        let mut versus = Self::new(stream)?;
+
+        let (seed, difficulty) = match versus.wait_for_message()? {
+            Message::Hello {
+                version,
+                game: Some(game),
+            } => {
+                check_version(version)?;
+                game
+            }
+            _ => return Err("the host didn't say which game to play".to_string()),
+        };
+        versus.send(&Message::Hello {
+            version: PROTOCOL_VERSION,
+            game: None,
+        });
+
+        versus.start_playing()?;
+        Ok((versus, seed, difficulty))
+    }
+
+    fn new(stream: TcpStream) -> Result<Self, String> {
+        stream
+            .set_read_timeout(Some(Duration::from_secs(30)))
+            .map_err(|e| e.to_string())?;
+        // Messages are tiny, sending them right away matters more than batching them
+        stream.set_nodelay(true).map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            stream,
+            received: Vec::new(),
+            pending: Vec::new(),
+            connected: true,
+            sent_state: None,
+            lost: false,
+            opponent: Opponent {
+                lives: 3,
+                ..Default::default()
+            },
+        })
+    }
+
+    /// From here on, reading never blocks the game.
+    fn start_playing(&mut self) -> Result<(), String> {
+        self.stream.set_nonblocking(true).map_err(|e| e.to_string())
+    }
+
+    pub fn opponent(&self) -> &Opponent {
+        &self.opponent
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    fn send(&mut self, message: &Message) {
+        if !self.connected {
+            return;
+        }
+
+        self.pending.extend_from_slice(message.to_line().as_bytes());
+        self.flush();
+    }
+
+    /// Sends as much of what is pending as the connection takes without blocking, the rest
+    /// waits for the next frame.
+    fn flush(&mut self) {
+        while self.connected && !self.pending.is_empty() {
+            match self.stream.write(&self.pending) {
+                Ok(0) => {
+                    warn!("The opponent left");
+                    self.connected = false;
+                }
+                Ok(written) => {
+                    self.pending.drain(..written);
+                }
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) if error.kind() == ErrorKind::Interrupted => (),
+                Err(error) => {
+                    warn!("Lost the connection to the opponent: {}", error);
+                    self.connected = false;
+                }
+            }
+        }
+    }
+
+    fn wait_for_message(&mut self) -> Result<Message, String> {
+        loop {
+            if let Some(message) = self.next_message() {
+                return Ok(message);
+            }
+            if !self.fill_buffer().map_err(|e| e.to_string())? {
+                return Err("the opponent left".to_string());
+            }
+        }
+    }
+
+    /// Reads whatever has arrived, returning whether the connection is still open.
+    fn fill_buffer(&mut self) -> std::io::Result<bool> {
+        let mut buffer = [0; 512];
+        match self.stream.read(&mut buffer) {
+            Ok(0) => Ok(false),
+            Ok(read) => {
+                self.received.extend_from_slice(&buffer[..read]);
+                Ok(true)
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    fn next_message(&mut self) -> Option<Message> {
+        while let Some(end) = self.received.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.received.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            match Message::parse(&line) {
+                Some(message) => return Some(message),
+                None => warn!(
+                    "Ignoring an unknown message from the opponent: {}",
+                    line.trim()
+                ),
+            }
+        }
+        None
+    }
+
+    /// Everything the opponent sent since the last frame.
+    fn receive(&mut self) -> Vec<Message> {
+        self.flush();
+        while self.connected {
+            match self.fill_buffer() {
+                Ok(true) => continue,
+                Ok(false) => {
+                    warn!("The opponent left");
+                    self.connected = false;
+                }
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) => {
+                    warn!("Lost the connection to the opponent: {}", error);
+                    self.connected = false;
+                }
+            }
+        }
+
+        let mut messages = Vec::new();
+        while let Some(message) = self.next_message() {
+            messages.push(message);
+        }
+        messages
+    }
+}
+
+fn check_version(version: u32) -> Result<(), String> {
+    if version == PROTOCOL_VERSION {
+        Ok(())
+    } else {
+        Err(format!(
+            "the opponent speaks version {} of the protocol, this game speaks {}",
+            version, PROTOCOL_VERSION
+        ))
+    }
+}
+
+fn exchange_messages(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+    mut versus: ResMut<Versus>,
+    game_state: Res<GameState>,
+    arena: Res<Arena>,
+    theme: Res<Theme>,
+    registry: Res<ObjectRegistry>,
+    mut game_started: EventReader<GameStarted>,
+    mut game_over: EventReader<GameOver>,
+    players: Query<(&Player, &Scoreboard, &Lives)>,
+    combos: Query<&Combo, (With<Player>, Changed<Combo>)>,
+) {
+    for message in versus.receive() {
+        match message {
+            Message::State { score, lives } => {
+                versus.opponent.score = score;
+                versus.opponent.lives = lives;
+            }
+            Message::Garbage(count) if !game_state.is_not_running() => {
+                // Only this side places garbage, drawing it from the seeded run would leave
+                // the two sides on different ones. A few stars at a time is all a combo
+                // earns, larger counts only come from a misbehaving opponent.
+                for _ in 0..u64::from(count).min(COMBO_FOR_GARBAGE) {
+                    let x = thread_rng().gen_range(arena.spawn_x_range());
+                    spawn_object(
+                        &mut commands,
+                        &mut materials,
+                        &asset_server,
+                        &theme,
+                        &registry,
+                        ObjectKind::STAR,
+                        Vec2::new(x, arena.spawn_y),
+                    );
+                }
+            }
+            Message::Over { score } => {
+                versus.opponent.score = score;
+                versus.opponent.lost = true;
+            }
+            Message::Garbage(_) | Message::Hello { .. } => (),
+        }
+    }
+
+    if game_started.iter().next().is_some() {
+        versus.lost = false;
+        versus.opponent.lost = false;
+    }
+
+    // Only the first player plays over the network
+    let local = players.iter().find(|(player, _, _)| player.0 == 0);
+    let (score, lives) = match local {
+        Some((_, scoreboard, lives)) => (scoreboard.score(), lives.count()),
+        None => return,
+    };
+
+    if versus.sent_state != Some((score, lives)) {
+        versus.send(&Message::State { score, lives });
+        versus.sent_state = Some((score, lives));
+    }
+
+    for combo in combos.iter() {
+        if combo.current > 0 && combo.current % COMBO_FOR_GARBAGE == 0 {
+            versus.send(&Message::Garbage(1));
+        }
+    }
+
+    if game_over.iter().next().is_some() {
+        versus.lost = true;
+        versus.send(&Message::Over { score });
+    }
+}
+
+struct OpponentBoard;
+
+fn spawn_opponent_board(mut commands: Commands, fonts: Res<Fonts>) {
+    commands
+        .spawn_bundle(TextBundle {
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: fonts.ui.clone(),
+                    font_size: 24.0,
+                    color: Color::rgb(0.0, 0.0, 0.0),
+                },
+                Default::default(),
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(OpponentBoard)
+        .insert(HudAnchor::top(5.0, 235.0).with_font_size(24.0));
+}
+
+fn update_opponent_board(
+    versus: Res<Versus>,
+    localization: Res<Localization>,
+    mut boards: Query<&mut Text, With<OpponentBoard>>,
+) {
+    let opponent = versus.opponent();
+    let own_score = versus.sent_state.map_or(0, |(score, _)| score);
+    let key = match (versus.is_connected(), versus.lost, opponent.lost) {
+        (false, _, _) => "versus-disconnected",
+        (true, true, true) if own_score > opponent.score => "versus-won",
+        (true, true, true) if own_score < opponent.score => "versus-lost",
+        (true, true, true) => "versus-draw",
+        (true, _, true) => "versus-opponent-out",
+        (true, _, false) => "versus-opponent",
+    };
+    let value = localization.format(
+        key,
+        &[
+            ("score", opponent.score.to_string()),
+            ("lives", opponent.lives.to_string()),
+        ],
+    );
+
+    for mut text in boards.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{thread, time::Instant};
+
+    const PATIENCE: Duration = Duration::from_secs(5);
+
+    /// Waits a while for the next message the other side sends.
+    fn receive_one(versus: &mut Versus) -> Message {
+        let start = Instant::now();
+        while start.elapsed() < PATIENCE {
+            if let Some(message) = versus.receive().into_iter().next() {
+                return message;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("no message arrived");
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        assert!(check_version(PROTOCOL_VERSION).is_ok());
+
+        let line = format!("hello {} 42 normal", PROTOCOL_VERSION + 1);
+        match Message::parse(&line) {
+            Some(Message::Hello { version, .. }) => assert!(check_version(version).is_err()),
+            message => panic!("expected a hello, got {:?}", message),
+        }
+    }
+
+    #[test]
+    fn plays_over_localhost() {
+        // Finds a free port, which is all but certain to stay free for the host to take
+        let address = TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .unwrap();
+        let host = thread::spawn(move || Versus::host(address, 42, Difficulty::Hard));
+
+        let start = Instant::now();
+        let (mut joined, seed, difficulty) = loop {
+            match Versus::join(&address.to_string()) {
+                Ok(joined) => break joined,
+                Err(_) if start.elapsed() < PATIENCE => thread::sleep(Duration::from_millis(10)),
+                Err(error) => panic!("could not join the host: {}", error),
+            }
+        };
+        let mut host = host.join().unwrap().unwrap();
+        assert_eq!((seed, difficulty), (42, Difficulty::Hard));
+
+        host.send(&Message::Garbage(2));
+        assert_eq!(receive_one(&mut joined), Message::Garbage(2));
+        joined.send(&Message::State { score: 7, lives: 2 });
+        assert_eq!(
+            receive_one(&mut host),
+            Message::State { score: 7, lives: 2 }
+        );
+        assert!(host.is_connected());
+
+        drop(joined);
+        let start = Instant::now();
+        while host.is_connected() && start.elapsed() < PATIENCE {
+            host.receive();
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(!host.is_connected());
+    }
+}
@@ -0,0 +1,135 @@
+use crate::settings::Difficulty;
+
+/// Bumped whenever a message changes, instances only play against the same version.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Everything sent between two instances, one message per line.
+///
+/// The host opens with `hello VERSION SEED DIFFICULTY`, which the other instance answers
+/// with `hello VERSION`. After that, both send:
+/// - `state SCORE LIVES` whenever their score or lives change
+/// - `garbage COUNT` to drop extra stars on the opponent
+/// - `over SCORE` once they lose
+#[derive(Debug, PartialEq)]
+pub enum Message {
+    Hello {
+        version: u32,
+        game: Option<(u64, Difficulty)>,
+    },
+    State {
+        score: u64,
+        lives: u64,
+    },
+    Garbage(u32),
+    Over {
+        score: u64,
+    },
+}
+
+impl Message {
+    pub fn to_line(&self) -> String {
+        match self {
+            Self::Hello {
+                version,
+                game: Some((seed, difficulty)),
+            } => format!("hello {} {} {}\n", version, seed, difficulty.name()),
+            Self::Hello {
+                version,
+                game: None,
+            } => format!("hello {}\n", version),
+            Self::State { score, lives } => format!("state {} {}\n", score, lives),
+            Self::Garbage(count) => format!("garbage {}\n", count),
+            Self::Over { score } => format!("over {}\n", score),
+        }
+    }
+
+    pub fn parse(line: &str) -> Option<Self> {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let message = match parts.as_slice() {
+            ["hello", version] => Self::Hello {
+                version: version.parse().ok()?,
+                game: None,
+            },
+            ["hello", version, seed, difficulty] => Self::Hello {
+                version: version.parse().ok()?,
+                game: Some((seed.parse().ok()?, Difficulty::from_name(difficulty)?)),
+            },
+            ["state", score, lives] => Self::State {
+                score: score.parse().ok()?,
+                lives: lives.parse().ok()?,
+            },
+            ["garbage", count] => Self::Garbage(count.parse().ok()?),
+            ["over", score] => Self::Over {
+                score: score.parse().ok()?,
+            },
+            _ => return None,
+        };
+        Some(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        let messages = [
+            Message::Hello {
+                version: PROTOCOL_VERSION,
+                game: Some((u64::MAX, Difficulty::Easy)),
+            },
+            Message::Hello {
+                version: PROTOCOL_VERSION,
+                game: None,
+            },
+            Message::State {
+                score: 120,
+                lives: 3,
+            },
+            Message::Garbage(2),
+            Message::Over { score: 0 },
+        ];
+
+        for message in messages.iter() {
+            let line = message.to_line();
+            assert!(line.ends_with('\n'));
+            assert_eq!(Message::parse(&line).as_ref(), Some(message));
+        }
+    }
+
+    #[test]
+    fn ignores_extra_whitespace() {
+        assert_eq!(
+            Message::parse("  state   5\t1 \r\n"),
+            Some(Message::State { score: 5, lives: 1 })
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        let lines = [
+            "",
+            "\n",
+            "hello",
+            "hello one",
+            "hello 1 42",
+            "hello 1 42 impossible",
+            "hello 1 -42 normal",
+            "state 1",
+            "state 1 2 3",
+            "state one 2",
+            "garbage",
+            "garbage -1",
+            "garbage 1.5",
+            "over",
+            "over 1 2",
+            "bye 1",
+            "HELLO 1",
+        ];
+
+        for line in lines.iter() {
+            assert_eq!(Message::parse(line), None, "{:?}", line);
+        }
+    }
+}